@@ -0,0 +1,57 @@
+use criterion::{ black_box, criterion_group, criterion_main, BatchSize, Criterion };
+use minesweeper::gym::{ Action, MineEnv, RewardScheme };
+use minesweeper::layout::{ SeededGenerator, UniformGenerator };
+use minesweeper::mine_core::{ MinePlayground, Position };
+use minesweeper::shape::BoardShape;
+
+fn generation(c: &mut Criterion) {
+    c.bench_function("generate expert", |b| b.iter(|| {
+        MinePlayground::generate(BoardShape::rectangle(30, 16), black_box(&99), &UniformGenerator).unwrap()
+    }));
+    c.bench_function("generate 199x199", |b| b.iter(|| {
+        MinePlayground::generate(BoardShape::rectangle(199, 199), black_box(&8000), &UniformGenerator).unwrap()
+    }));
+    // what bots playing game after game pay
+    let mp = MinePlayground::generate(BoardShape::rectangle(30, 16), &99, &UniformGenerator).unwrap();
+    c.bench_function("regenerate expert", |b| b.iter(|| {
+        mp.regenerate(black_box(&99), &UniformGenerator).unwrap()
+    }));
+}
+
+fn flood_fill(c: &mut Criterion) {
+    // a sparse board opens almost entirely with one click
+    let mp = MinePlayground::generate(BoardShape::rectangle(199, 199), &40, &SeededGenerator(1)).unwrap();
+    let layout = mp.mine_layout();
+    let start = (0..layout.len()).find(|&i| !layout[i]).unwrap();
+    let start = mp.shape.position(start);
+    c.bench_function("flood fill 199x199", |b| b.iter_batched(
+        || MinePlayground::from_layout(BoardShape::rectangle(199, 199), &layout).unwrap(),
+        |mut mp| {
+            mp.click(black_box(&start));
+            mp
+        },
+        BatchSize::LargeInput,
+    ));
+    c.bench_function("click expert", |b| b.iter(|| {
+        let mut mp = MinePlayground::generate(BoardShape::rectangle(30, 16), &99, &SeededGenerator(2)).unwrap();
+        mp.click(black_box(&Position::new(15, 8, 0)));
+        mp
+    }));
+}
+
+/// Whole games of an agent revealing blocks in order, the way training runs them.
+fn gym_episodes(c: &mut Criterion) {
+    let mut env = MineEnv::new(BoardShape::rectangle(9, 9), 10, 1, RewardScheme::default()).unwrap();
+    let mut seed = 0;
+    c.bench_function("gym beginner episode", |b| b.iter(|| {
+        seed += 1;
+        env.reset(seed);
+        let mut index = 0;
+        while !env.step(Action::Reveal(index)).2 {
+            index += 1;
+        }
+    }));
+}
+
+criterion_group!(benches, generation, flood_fill, gym_episodes);
+criterion_main!(benches);
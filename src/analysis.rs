@@ -0,0 +1,203 @@
+use std::fmt;
+use crate::board_file::{ ReplayAction, ReplayEvent };
+use crate::mine_core::{ BlockStatus, ClickResult, MinePlayground, Position };
+use crate::solver::{ Cell, Knowledge };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveKind {
+    /// the first click, before any number is shown
+    Opening,
+    /// the numbers proved the block safe
+    Safe,
+    /// no block was certainly safe, with the chance the block held a mine
+    ForcedGuess(f64),
+    /// a guess while a block was certainly safe
+    AvoidableRisk(f64),
+}
+
+/// A block opened by a click or a chord, and what the player could know
+/// about it at that time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveReport {
+    pub time: f32,
+    pub pos: Position,
+    pub kind: MoveKind,
+    pub hit_mine: bool,
+}
+
+/// Plays `events` again on the mines of the finished game `end`, from the
+/// blocks in `start_shown`, e.g. the opening of no-guess boards, and tells
+/// for every block opened on purpose whether it was safe, a forced guess or
+/// an avoidable risk. Flood filled blocks aren't moves of their own.
+pub fn analyse(end: &MinePlayground, start_shown: &[usize], events: &[ReplayEvent]) -> Result<Vec<MoveReport>, String> {
+    if end.max_stack > 1 {
+        return Err(String::from("no analysis with stacked mines"));
+    }
+    let mut mp = MinePlayground::from_stacked_layout(end.shape.clone(), &end.mine_counts(), end.max_stack)?;
+    mp.lives = end.lives + end.exploded.len();
+    for &index in start_shown {
+        mp.show(&end.shape.position(index))?;
+    }
+    let mut reports = vec![];
+    for event in events {
+        let targets = match event.action {
+            ReplayAction::Flag => {
+                mp.right_click(&event.pos);
+                continue;
+            }
+            ReplayAction::Reveal => vec![event.pos],
+            ReplayAction::Chord => mp.neighbours(&event.pos),
+        };
+        let targets: Vec<Position> = targets.into_iter()
+            .filter(|pos| mp.shape.is_playable(pos) && mp.block(pos).bstatus == BlockStatus::Hidden)
+            .collect();
+        let knowledge = Knowledge::from_playground(&mp);
+        let result = match event.action {
+            ReplayAction::Chord => mp.chord(&event.pos),
+            _ => mp.click(&event.pos),
+        };
+        for pos in targets.into_iter().filter(|pos| mp.block(pos).bstatus == BlockStatus::Shown) {
+            reports.push(MoveReport {
+                time: event.time,
+                pos,
+                kind: classify(&knowledge, mp.shape.index(&pos)),
+                hit_mine: mp.block(&pos).btype.mine_count() > 0,
+            });
+        }
+        if let ClickResult::Wasted = result {
+            break;
+        }
+    }
+    Ok(reports)
+}
+
+fn classify(knowledge: &Knowledge, index: usize) -> MoveKind {
+    if knowledge.cells.iter().all(|cell| !matches!(cell, Cell::Shown(_))) {
+        return MoveKind::Opening;
+    }
+    if knowledge.proves(index, false) {
+        return MoveKind::Safe;
+    }
+    let chance = knowledge.mine_probabilities()[index];
+    // the quick rules first, the exact check only when they find nothing
+    let safe_move = knowledge.deduce().iter().any(|deduction| !deduction.is_mine)
+        || knowledge.certain().iter().any(|deduction| !deduction.is_mine);
+    if safe_move { MoveKind::AvoidableRisk(chance) } else { MoveKind::ForcedGuess(chance) }
+}
+
+/// One character a move: `o` the opening, `.` safe, `g` a forced guess, `!`
+/// an avoidable risk, `X` a mine hit.
+pub fn timeline(reports: &[MoveReport]) -> String {
+    reports.iter().map(|report| match report.kind {
+        _ if report.hit_mine => 'X',
+        MoveKind::Opening => 'o',
+        MoveKind::Safe => '.',
+        MoveKind::ForcedGuess(_) => 'g',
+        MoveKind::AvoidableRisk(_) => '!',
+    }).collect()
+}
+
+/// The guesses and risks taken, and what the first mine hit was: bad luck or
+/// a blunder.
+pub fn summary(reports: &[MoveReport]) -> String {
+    let guesses = reports.iter().filter(|report| matches!(report.kind, MoveKind::ForcedGuess(_))).count();
+    let risks = reports.iter().filter(|report| matches!(report.kind, MoveKind::AvoidableRisk(_))).count();
+    let mut text = format!("{} forced guesses, {} avoidable risks", guesses, risks);
+    if let Some(death) = reports.iter().find(|report| report.hit_mine) {
+        text += &match death.kind {
+            MoveKind::ForcedGuess(chance) => format!(", hit a mine at {:.1}s: bad luck ({:.0}% mine)", death.time, chance * 100.0),
+            MoveKind::AvoidableRisk(chance) => format!(", hit a mine at {:.1}s: a safe block was left ({:.0}% mine)", death.time, chance * 100.0),
+            _ => format!(", hit a mine at {:.1}s", death.time),
+        };
+    }
+    text
+}
+
+impl fmt::Display for MoveReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:6.1}s ({}, {}, {}) ", self.time, self.pos.x, self.pos.y, self.pos.z)?;
+        match self.kind {
+            MoveKind::Opening => write!(f, "opening")?,
+            MoveKind::Safe => write!(f, "safe")?,
+            MoveKind::ForcedGuess(chance) => write!(f, "forced guess, {:.0}% mine", chance * 100.0)?,
+            MoveKind::AvoidableRisk(chance) => write!(f, "avoidable risk, {:.0}% mine", chance * 100.0)?,
+        }
+        if self.hit_mine {
+            write!(f, ", hit a mine")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::BoardShape;
+    fn event(time: f32, action: ReplayAction, x: usize, y: usize) -> ReplayEvent {
+        ReplayEvent { time, action, pos: Position::new(x, y, 0) }
+    }
+    #[test]
+    fn test_analyse() {
+        // one mine in the bottom left corner, the opening leaves a 1 next to it
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        let mut end = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        end.click(&Position::new(4, 4, 0));
+        let events = [
+            event(0.5, ReplayAction::Reveal, 4, 4),
+            event(1.0, ReplayAction::Flag, 0, 0),
+            event(2.0, ReplayAction::Reveal, 0, 0),
+        ];
+        let reports = analyse(&end, &[], &events).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].kind, MoveKind::Opening);
+        // a click on the only hidden block, the mine
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&Position::new(4, 4, 0));
+        mp.click(&Position::new(0, 0, 0));
+        let events = [event(0.5, ReplayAction::Reveal, 4, 4), event(3.0, ReplayAction::Reveal, 0, 0)];
+        let reports = analyse(&mp, &[], &events).unwrap();
+        assert_eq!(timeline(&reports), "oX");
+        assert!(reports[1].hit_mine);
+        assert!(summary(&reports).contains("hit a mine at 3.0s"));
+    }
+    #[test]
+    fn test_classify() {
+        // 1-2-1 under a hidden top row, everything else shown
+        let mut layout = vec![false; 25];
+        layout[21] = true;
+        layout[23] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        for index in 0..20 {
+            mp.show(&mp.shape.position(index)).unwrap();
+        }
+        let knowledge = Knowledge::from_playground(&mp);
+        assert_eq!(classify(&knowledge, 22), MoveKind::Safe);
+        // with the numbers away from the top row, every block is a guess
+        let knowledge = Knowledge::from_playground(&MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap());
+        assert_eq!(classify(&knowledge, 22), MoveKind::Opening);
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.show(&Position::new(0, 0, 0)).unwrap();
+        let knowledge = Knowledge::from_playground(&mp);
+        assert!(matches!(classify(&knowledge, 24), MoveKind::AvoidableRisk(_)));
+    }
+    #[test]
+    fn test_forced_guess_chance() {
+        // numbers around an opening and no safe block, the bottom left corner
+        // away from them is a mine in 24% of the layouts, not in 35%
+        let mut layout = vec![false; 49];
+        for index in [2, 6, 9, 16, 19, 27, 29, 34, 35, 42, 44, 47, 48] {
+            layout[index] = true;
+        }
+        let mut end = MinePlayground::from_layout(BoardShape::rectangle(7, 7), &layout).unwrap();
+        end.click(&Position::new(4, 4, 0));
+        end.click(&Position::new(0, 0, 0));
+        let events = [event(0.5, ReplayAction::Reveal, 4, 4), event(2.0, ReplayAction::Reveal, 0, 0)];
+        let reports = analyse(&end, &[], &events).unwrap();
+        assert_eq!(timeline(&reports), "og");
+        match reports[1].kind {
+            MoveKind::ForcedGuess(chance) => assert!((chance - 0.244).abs() < 0.001),
+            kind => panic!("{:?}", kind),
+        }
+    }
+}
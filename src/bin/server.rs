@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::io::{ BufRead, BufReader, Write };
+use std::net::{ TcpListener, TcpStream };
+use std::sync::mpsc::{ self, Sender };
+use std::{ env, thread };
+use minesweeper::net::{ self, ClientMessage, NetMode, Recipient, Room, ServerMessage };
+
+const USAGE: &str = "usage: ./server [--port NUM] [--mode coop|race] [--level NUM] [--seed NUM]";
+
+/// What the connection threads tell the room, by connection number.
+enum Event {
+    Connected(usize, TcpStream),
+    Line(usize, String),
+    Left(usize),
+}
+
+/// The room lives on the main thread, every connection has a thread reading
+/// its lines. Only localhost is listened to.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let config_map: Vec<(usize, usize, usize)> = vec![(8, 8, 10), (16, 16, 40), (30, 16, 99)];
+    let mut port = net::DEFAULT_PORT;
+    let mut mode = NetMode::Coop;
+    let mut level = 0;
+    let mut seed = 0;
+    if args.len().is_multiple_of(2) {
+        panic!("{}", USAGE);
+    }
+    for pair in args[1..].chunks(2) {
+        match pair[0].as_str() {
+            "--port" => port = pair[1].parse().expect("port must be a number"),
+            "--mode" => mode = NetMode::from_name(&pair[1]).unwrap_or_else(|e| panic!("{}", e)),
+            "--level" => level = pair[1].parse().expect("level must be a number"),
+            "--seed" => seed = pair[1].parse().expect("seed must be a number"),
+            _ => panic!("{}", USAGE),
+        }
+    }
+    let (width, height, mine_count) = *config_map.get(level).expect("levels are 0, 1 and 2");
+    let mut room = Room::new(mode, width, height, mine_count, seed).unwrap_or_else(|e| panic!("{}", e));
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| panic!("cannot listen on port {}: {}", port, e));
+    println!("{} server on 127.0.0.1:{}, {}x{} with {} mines", mode.name(), port, width, height, mine_count);
+    let (sender, events) = mpsc::channel();
+    thread::spawn(move || accept(listener, sender));
+    let mut streams: HashMap<usize, TcpStream> = HashMap::new();
+    // connection number to player, once joined
+    let mut players: HashMap<usize, usize> = HashMap::new();
+    for event in events {
+        let (connection, line) = match event {
+            Event::Connected(connection, stream) => {
+                streams.insert(connection, stream);
+                continue;
+            }
+            Event::Left(connection) => {
+                streams.remove(&connection);
+                if let Some(id) = players.remove(&connection) {
+                    println!("{} left", room.players[id]);
+                }
+                continue;
+            }
+            Event::Line(connection, line) => (connection, line),
+        };
+        let out = match (ClientMessage::parse(&line), players.get(&connection)) {
+            (Ok(ClientMessage::Join(name)), None) => match room.join(&name) {
+                Ok((id, out)) => {
+                    println!("{} joined as player {}", name, id);
+                    players.insert(connection, id);
+                    out
+                }
+                Err(e) => {
+                    send(&streams[&connection], &ServerMessage::Error(e));
+                    continue;
+                }
+            },
+            (Ok(message), Some(&id)) => room.handle(id, message),
+            (Ok(_), None) => {
+                send(&streams[&connection], &ServerMessage::Error(String::from("send `join NAME` first")));
+                continue;
+            }
+            (Err(e), _) => {
+                send(&streams[&connection], &ServerMessage::Error(e));
+                continue;
+            }
+        };
+        for (recipient, message) in out.iter() {
+            for (connection, &id) in players.iter() {
+                if *recipient == Recipient::All || *recipient == Recipient::Only(id) {
+                    send(&streams[connection], message);
+                }
+            }
+        }
+    }
+}
+
+fn accept(listener: TcpListener, sender: Sender<Event>) {
+    for (connection, stream) in listener.incoming().enumerate() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                println!("connection failed: {}", e);
+                continue;
+            }
+        };
+        let reader = match stream.try_clone() {
+            Ok(reader) => BufReader::new(reader),
+            Err(_) => continue,
+        };
+        if sender.send(Event::Connected(connection, stream)).is_err() {
+            return;
+        }
+        let sender = sender.clone();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if sender.send(Event::Line(connection, line)).is_err() {
+                    break;
+                }
+            }
+            let _ = sender.send(Event::Left(connection));
+        });
+    }
+}
+
+/// Clients gone are noticed by their reading thread, so failed writes are
+/// left alone.
+fn send(stream: &TcpStream, message: &ServerMessage) {
+    let _ = writeln!(&*stream, "{}", message);
+}
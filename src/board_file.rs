@@ -0,0 +1,343 @@
+use crate::mine_core::{ MinePlayground, Position };
+use crate::neighbourhood::Neighbourhood;
+use crate::shape::BoardShape;
+use crate::topology::Topology;
+
+const CODE_PREFIX: &str = "MS1-";
+/// base64 with the URL-safe characters, so codes survive chats and links
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+/// codes store these by index
+const TOPOLOGIES: [Topology; 3] = [Topology::Rectangle, Topology::Torus, Topology::Hex];
+const NEIGHBOURHOODS: [Neighbourhood; 4] = [Neighbourhood::Classic, Neighbourhood::Knight, Neighbourhood::Cross, Neighbourhood::Radius2];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplayAction {
+    Reveal,
+    Flag,
+    /// opening around a number, a middle release in RAWVF files
+    Chord,
+}
+#[derive(Debug, Clone, Copy)]
+pub struct ReplayEvent {
+    pub time: f32,
+    pub action: ReplayAction,
+    pub pos: Position,
+}
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub width: usize,
+    pub height: usize,
+    pub layout: Vec<bool>,
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    pub fn playground(&self) -> Result<MinePlayground, String> {
+        MinePlayground::from_layout(BoardShape::rectangle(self.width, self.height), &self.layout)
+    }
+    /// The time of the last move, the finishing one of a won game.
+    pub fn duration(&self) -> f32 {
+        self.events.last().map_or(0.0, |event| event.time)
+    }
+}
+
+/// Reads a Minesweeper Arbiter `.mbf` board: one byte each for width and height,
+/// a big-endian u16 mine count, then an (x, y) byte pair per mine, y = 0 being
+/// the top row where the board has it at the bottom.
+pub fn read_mbf(bytes: &[u8]) -> Result<MinePlayground, String> {
+    if bytes.len() < 4 {
+        return Err(String::from("mbf: file too short"));
+    }
+    let width = bytes[0] as usize;
+    let height = bytes[1] as usize;
+    let mine_count = ((bytes[2] as usize) << 8) | bytes[3] as usize;
+    if bytes.len() < 4 + mine_count * 2 {
+        return Err(format!("mbf: expected {} mines, file is truncated", mine_count));
+    }
+    let mut layout = vec![false; width * height];
+    for mine in bytes[4..4 + mine_count * 2].chunks(2) {
+        let (x, y) = (mine[0] as usize, mine[1] as usize);
+        if x >= width || y >= height {
+            return Err(format!("mbf: mine ({}, {}) is out of the board", x, y));
+        }
+        let index = (height - 1 - y) * width + x;
+        if layout[index] {
+            return Err(format!("mbf: duplicated mine ({}, {})", x, y));
+        }
+        layout[index] = true;
+    }
+    MinePlayground::from_layout(BoardShape::rectangle(width, height), &layout)
+}
+
+pub fn write_mbf(mp: &MinePlayground) -> Result<Vec<u8>, String> {
+    if mp.shape.playable_count() != mp.width * mp.height || mp.shape.depth > 1 {
+        return Err(String::from("mbf: only full rectangular boards can be saved"));
+    }
+    if mp.mine_counts().iter().any(|&count| count > 1) {
+        return Err(String::from("mbf: stacked mines can't be saved"));
+    }
+    if mp.width > 255 || mp.height > 255 {
+        return Err(String::from("mbf: board is larger than 255x255"));
+    }
+    let mines: Vec<(usize, usize)> = mp.mine_layout().iter().enumerate()
+        .filter(|(_, &is_mine)| is_mine)
+        .map(|(i, _)| (i % mp.width, mp.height - 1 - i / mp.width))
+        .collect();
+    if mines.len() > u16::MAX as usize {
+        return Err(String::from("mbf: too many mines"));
+    }
+    let mut bytes = vec![mp.width as u8, mp.height as u8, (mines.len() >> 8) as u8, mines.len() as u8];
+    for (x, y) in mines {
+        bytes.push(x as u8);
+        bytes.push(y as u8);
+    }
+    Ok(bytes)
+}
+
+/// A board as a short text to paste, `MS1-` then base64 of: width, height,
+/// depth, topology, neighbourhood, most mines per block, lives and whether a
+/// mask follows, one byte each; the mask of one layer, 1 bit per block; the
+/// mines of every block, 1 bit each or 2 with stacked mines; a checksum byte.
+pub fn write_code(mp: &MinePlayground) -> Result<String, String> {
+    let shape = &mp.shape;
+    let lives = mp.lives + mp.exploded.len();
+    if shape.depth > 255 || lives > 255 {
+        return Err(String::from("code: too many layers or lives"));
+    }
+    let masked = shape.mask.iter().any(|&playable| !playable);
+    let topology = TOPOLOGIES.iter().position(|&cur| cur == shape.topology).unwrap();
+    let neighbourhood = NEIGHBOURHOODS.iter().position(|&cur| cur == shape.neighbourhood).unwrap();
+    let mut bytes = vec![
+        mp.width as u8, mp.height as u8, shape.depth as u8, topology as u8,
+        neighbourhood as u8, mp.max_stack as u8, lives as u8, masked as u8,
+    ];
+    let mut bits = vec![];
+    if masked {
+        bits.extend(shape.mask[..mp.width * mp.height].iter().map(|&playable| playable as u8));
+    }
+    for count in mp.mine_counts() {
+        bits.push((count & 1) as u8);
+        if mp.max_stack > 1 {
+            bits.push((count >> 1) as u8);
+        }
+    }
+    bytes.extend(bits.chunks(8).map(|chunk| chunk.iter().enumerate().fold(0, |byte, (i, &bit)| byte | bit << i)));
+    bytes.push(checksum(&bytes));
+    let mut code = String::from(CODE_PREFIX);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| group | (byte as u32) << (16 - 8 * i));
+        for i in 0..chunk.len() + 1 {
+            code.push(CODE_ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    Ok(code)
+}
+
+/// Reads a board written by `write_code`, lives included.
+pub fn read_code(code: &str) -> Result<MinePlayground, String> {
+    let data = code.trim().strip_prefix(CODE_PREFIX).ok_or("code: not a board code")?;
+    let mut sextets = vec![];
+    for c in data.bytes() {
+        sextets.push(CODE_ALPHABET.iter().position(|&cur| cur == c).ok_or(format!("code: bad character `{}`", c as char))? as u32);
+    }
+    let mut bytes = vec![];
+    for chunk in sextets.chunks(4) {
+        let group = chunk.iter().enumerate().fold(0, |group, (i, &sextet)| group | sextet << (18 - 6 * i));
+        for i in 0..chunk.len().saturating_sub(1) {
+            bytes.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    if bytes.len() < 9 || checksum(&bytes[..bytes.len() - 1]) != bytes[bytes.len() - 1] {
+        return Err(String::from("code: damaged, check it was copied whole"));
+    }
+    let (width, height, depth) = (bytes[0] as usize, bytes[1] as usize, bytes[2] as usize);
+    let topology = *TOPOLOGIES.get(bytes[3] as usize).ok_or("code: unknown topology")?;
+    let neighbourhood = *NEIGHBOURHOODS.get(bytes[4] as usize).ok_or("code: unknown neighbourhood")?;
+    let (max_stack, lives, masked) = (bytes[5] as usize, bytes[6] as usize, bytes[7] != 0);
+    if depth == 0 || lives == 0 {
+        return Err(String::from("code: no layers or no lives"));
+    }
+    let mut bits = bytes[8..bytes.len() - 1].iter().flat_map(|&byte| (0..8).map(move |i| (byte >> i & 1) as usize));
+    let mut next_bit = || bits.next().ok_or("code: truncated");
+    let mut mask = vec![true; width * height];
+    if masked {
+        for playable in mask.iter_mut() {
+            *playable = next_bit()? == 1;
+        }
+    }
+    let shape = BoardShape::with_mask(width, height, topology, mask).with_depth(depth).with_neighbourhood(neighbourhood)?;
+    let mut counts = vec![];
+    for _ in 0..shape.block_count() {
+        let low = next_bit()?;
+        counts.push(if max_stack > 1 { low | next_bit()? << 1 } else { low });
+    }
+    let mut mp = MinePlayground::from_stacked_layout(shape, &counts, max_stack)?;
+    mp.lives = lives;
+    Ok(mp)
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, &byte| sum.rotate_left(1) ^ byte)
+}
+
+/// Reads a RAW Video Format (RAWVF) replay. Only the header, the `Board:` grid
+/// (`*` for mines) and the left release, right press and middle release mouse
+/// events are used; cell coordinates in the file are 1-based, and rows go from
+/// the top down.
+pub fn read_rawvf(text: &str) -> Result<Replay, String> {
+    let mut width = None;
+    let mut height = None;
+    let mut mine_count = None;
+    let mut rows = vec![];
+    let mut events = vec![];
+    let mut section = "";
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match line {
+            "Board:" | "Events:" => {
+                section = line;
+                continue;
+            }
+            _ => {}
+        }
+        match section {
+            "Board:" => rows.push(line.chars().map(|c| c == '*').collect::<Vec<bool>>()),
+            "Events:" => {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() < 4 {
+                    continue;
+                }
+                let action = match fields[1] {
+                    "lr" => ReplayAction::Reveal,
+                    "rc" => ReplayAction::Flag,
+                    "mr" => ReplayAction::Chord,
+                    _ => continue,
+                };
+                let parse_field = |field: &str| field.parse::<usize>().map_err(|_| format!("rawvf: bad event line `{}`", line));
+                let (x, y) = (parse_field(fields[2])?, parse_field(fields[3])?);
+                // releases outside of the board are legal, they just do nothing
+                if x == 0 || y == 0 {
+                    continue;
+                }
+                events.push(ReplayEvent {
+                    time: fields[0].parse().map_err(|_| format!("rawvf: bad event time `{}`", line))?,
+                    action,
+                    pos: Position { x: x - 1, y: y - 1, z: 0 },
+                });
+            }
+            _ => {
+                if let Some(idx) = line.find(':') {
+                    let value = line[idx + 1..].trim().parse::<usize>().ok();
+                    match &line[..idx] {
+                        "Width" => width = value,
+                        "Height" => height = value,
+                        "Mines" => mine_count = value,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+    let width = width.ok_or("rawvf: missing Width")?;
+    let height = height.ok_or("rawvf: missing Height")?;
+    let layout: Vec<bool> = rows.into_iter().rev().flatten().collect();
+    if layout.len() != width * height {
+        return Err(String::from("rawvf: board does not match Width and Height"));
+    }
+    if let Some(count) = mine_count {
+        if layout.iter().filter(|&&is_mine| is_mine).count() != count {
+            return Err(String::from("rawvf: board does not match Mines"));
+        }
+    }
+    events.retain(|ev| ev.pos.x < width && ev.pos.y < height);
+    for event in events.iter_mut() {
+        event.pos.y = height - 1 - event.pos.y;
+    }
+    Ok(Replay { width, height, layout, events })
+}
+
+/// Writes a replay that `read_rawvf` reads back, with the pixel positions of
+/// 16 pixel blocks.
+pub fn write_rawvf(replay: &Replay) -> String {
+    let mine_count = replay.layout.iter().filter(|&&is_mine| is_mine).count();
+    let mut text = format!("RawVF_Version: Rev5\nWidth: {}\nHeight: {}\nMines: {}\nBoard:\n", replay.width, replay.height, mine_count);
+    for row in replay.layout.chunks(replay.width).rev() {
+        text.extend(row.iter().map(|&is_mine| if is_mine { '*' } else { '0' }));
+        text.push('\n');
+    }
+    text += "Events:\n0.00 start\n";
+    for event in replay.events.iter() {
+        let action = match event.action {
+            ReplayAction::Reveal => "lr",
+            ReplayAction::Flag => "rc",
+            ReplayAction::Chord => "mr",
+        };
+        let (x, y) = (event.pos.x + 1, replay.height - event.pos.y);
+        text += &format!("{:.2} {} {} {} ({} {})\n", event.time, action, x, y, x * 16 - 8, y * 16 - 8);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_mbf_round_trip() {
+        let mp = MinePlayground::init(&9, &7, &10).unwrap();
+        let bytes = write_mbf(&mp).unwrap();
+        assert_eq!(&bytes[..4], &[9, 7, 0, 10]);
+        assert_eq!(read_mbf(&bytes).unwrap().mine_layout(), mp.mine_layout());
+        assert!(read_mbf(&bytes[..10]).is_err());
+    }
+    #[test]
+    fn test_mbf_top_row() {
+        // 6x5, one mine in the top left corner and one right under the top right
+        let file = [6, 5, 0, 2, 0, 0, 5, 1];
+        let mp = read_mbf(&file).unwrap();
+        let layout = mp.mine_layout();
+        let mines: Vec<Position> = (0..30).filter(|&i| layout[i]).map(|i| mp.shape.position(i)).collect();
+        assert_eq!(mines, vec![Position::new(5, 3, 0), Position::new(0, 4, 0)]);
+        assert_eq!(write_mbf(&mp).unwrap(), vec![6, 5, 0, 2, 5, 1, 0, 0]);
+    }
+    #[test]
+    fn test_read_rawvf() {
+        let text = "RawVF_Version: Rev5\nWidth: 5\nHeight: 6\nMines: 2\nBoard:\n*0000\n00000\n00000\n00000\n00000\n000*0\n\
+            Events:\n0.00 start\n0.00 lc 3 3 (60 60)\n0.01 lr 3 3 (60 60)\n0.50 mv 5 5 (100 100)\n0.80 rc 1 1 (10 10)\n";
+        let replay = read_rawvf(text).unwrap();
+        assert_eq!(replay.playground().unwrap().mine_layout(), replay.layout);
+        // the top row of the file is the top row of the board
+        assert!(replay.layout[5 * 5] && replay.layout[3]);
+        assert_eq!(replay.layout.iter().filter(|&&is_mine| is_mine).count(), 2);
+        assert_eq!(replay.events.len(), 2);
+        assert_eq!(replay.events[0].action, ReplayAction::Reveal);
+        assert_eq!((replay.events[0].pos.x, replay.events[0].pos.y), (2, 3));
+        assert_eq!((replay.events[1].pos.x, replay.events[1].pos.y), (0, 5));
+        let text = write_rawvf(&replay);
+        assert!(text.contains("Board:\n*0000\n") && text.contains("0.80 rc 1 1 (8 8)"));
+        let written = read_rawvf(&text).unwrap();
+        assert_eq!((written.duration(), written.events.len(), written.layout), (0.8, 2, replay.layout));
+    }
+    #[test]
+    fn test_code_round_trip() {
+        let mp = MinePlayground::init(&30, &16, &99).unwrap();
+        let code = write_code(&mp).unwrap();
+        assert!(code.len() < 100);
+        let read = read_code(&code).unwrap();
+        assert_eq!((read.width, read.height, read.lives), (30, 16, 1));
+        assert_eq!(read.mine_layout(), mp.mine_layout());
+        // every variant rule travels with the board
+        let shape = BoardShape::from_name("donut", 12, 12, Topology::Torus).unwrap()
+            .with_depth(2)
+            .with_neighbourhood(Neighbourhood::Cross).unwrap();
+        let mut mp = MinePlayground::generate_stacked(shape, &20, 3, &crate::layout::UniformGenerator).unwrap();
+        mp.lives = 3;
+        let read = read_code(&write_code(&mp).unwrap()).unwrap();
+        assert_eq!(read.mine_counts(), mp.mine_counts());
+        assert_eq!(read.shape.mask, mp.shape.mask);
+        assert_eq!((read.shape.depth, read.shape.topology, read.shape.neighbourhood), (2, Topology::Torus, Neighbourhood::Cross));
+        assert_eq!((read.max_stack, read.lives), (3, 3));
+        let mut damaged = code.into_bytes();
+        damaged[10] = if damaged[10] == b'A' { b'B' } else { b'A' };
+        assert!(read_code(&String::from_utf8(damaged).unwrap()).is_err());
+        assert!(read_code("hello").is_err());
+    }
+}
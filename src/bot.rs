@@ -0,0 +1,146 @@
+use crate::layout::LayoutGenerator;
+use crate::mine_core::{ BlockStatus, BlockType, ClickResult, MinePlayground, Position };
+use crate::shape::BoardShape;
+use crate::solver::{ Cell, Knowledge };
+
+/// The moves a player has, the bot plays the same ones.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BotMove {
+    Reveal(Position),
+    /// a right click, also used to clear a flag from a block known to be safe
+    Flag(Position),
+    Chord(Position),
+}
+
+/// The bot's next move: flags the mines it can deduce, opens the blocks it
+/// knows are safe, by chording when the flags are already there, and guesses
+/// the least likely mine when stuck. `None` once the game is over, and on
+/// boards with stacked mines, which the solver can't read.
+pub fn next_move(mp: &MinePlayground) -> Option<BotMove> {
+    if mp.max_stack > 1 {
+        return None;
+    }
+    let mut knowledge = Knowledge::from_playground(mp);
+    if knowledge.cells.iter().all(|&cell| cell != Cell::Hidden) {
+        return None;
+    }
+    let status = |index: usize| mp.block_at(index).bstatus;
+    loop {
+        let deductions = knowledge.deduce();
+        let mut new_mines = false;
+        for deduction in deductions.iter().filter(|deduction| deduction.is_mine) {
+            if status(deduction.index) != BlockStatus::Flaged(1) {
+                return Some(BotMove::Flag(mp.shape.position(deduction.index)));
+            }
+            knowledge.cells[deduction.index] = Cell::Mine;
+            new_mines = true;
+        }
+        if let Some(safe) = deductions.iter().find(|deduction| !deduction.is_mine) {
+            let pos = mp.shape.position(safe.index);
+            if status(safe.index) != BlockStatus::Hidden {
+                return Some(BotMove::Flag(pos));
+            }
+            let chord = mp.neighbour_indices(safe.index).find(|&cur| is_chord_ready(mp, &knowledge, cur));
+            return Some(chord.map_or(BotMove::Reveal(pos), |cur| BotMove::Chord(mp.shape.position(cur))));
+        }
+        if !new_mines {
+            break;
+        }
+    }
+    let odds = knowledge.mine_probabilities();
+    // on a tie, corners open more often
+    (0..odds.len())
+        .filter(|&index| status(index) == BlockStatus::Hidden)
+        .min_by(|&a, &b| odds[a].partial_cmp(&odds[b]).unwrap()
+            .then(knowledge.neighbours[a].len().cmp(&knowledge.neighbours[b].len())))
+        .map(|index| BotMove::Reveal(mp.shape.position(index)))
+}
+
+/// Whether chording the block opens only safe blocks: a shown number whose
+/// flags are all on known mines, as many as the number.
+fn is_chord_ready(mp: &MinePlayground, knowledge: &Knowledge, index: usize) -> bool {
+    let tip = match mp.block_at(index) {
+        block if block.bstatus == BlockStatus::Shown => match block.btype {
+            BlockType::Tip(val) => val,
+            _ => return false,
+        },
+        _ => return false,
+    };
+    let flagged: Vec<usize> = mp.neighbour_indices(index)
+        .filter(|&cur| matches!(mp.block_at(cur).bstatus, BlockStatus::Flaged(_)))
+        .collect();
+    flagged.len() == tip && flagged.iter().all(|&cur| knowledge.cells[cur] == Cell::Mine)
+}
+
+pub fn play_move(mp: &mut MinePlayground, bot_move: BotMove) -> ClickResult {
+    match bot_move {
+        BotMove::Reveal(pos) => mp.click(&pos),
+        BotMove::Flag(pos) => {
+            mp.right_click(&pos);
+            ClickResult::NothingHappened
+        }
+        BotMove::Chord(pos) => mp.chord(&pos),
+    }
+}
+
+/// Lets the bot play `games` boards to the end and returns how many it won.
+/// Boards start like in the game: on the generator's safe start if it has
+/// one, and a mine under the first reveal is moved away if it allows that.
+pub fn play_games(shape: BoardShape, mine_count: usize, generator: &dyn LayoutGenerator, games: usize) -> Result<usize, String> {
+    let template = MinePlayground::generate(shape, &mine_count, generator)?;
+    let mut wins = 0;
+    for _ in 0..games {
+        let mut mp = template.regenerate(&mine_count, generator)?;
+        let mut result = match generator.safe_start(&mp.shape) {
+            Some(start) => mp.click(&start),
+            None => ClickResult::NothingHappened,
+        };
+        while let ClickResult::NothingHappened = result {
+            let bot_move = match next_move(&mp) {
+                Some(bot_move) => bot_move,
+                None => break,
+            };
+            if let BotMove::Reveal(pos) = bot_move {
+                if mp.shown_count == 0 && mp.block(&pos).btype.mine_count() > 0 && generator.allows_first_click_fix() {
+                    mp.fix(&pos);
+                }
+            }
+            result = play_move(&mut mp, bot_move);
+        }
+        if let ClickResult::Win = result {
+            wins += 1;
+        }
+    }
+    Ok(wins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::{ NoGuessGenerator, UniformGenerator };
+    #[test]
+    fn test_next_move() {
+        // opening the bottom rows leaves 1-1-2-1-1 under a hidden top row
+        let mut layout = vec![false; 25];
+        layout[1] = true;
+        layout[3] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&Position::new(2, 4, 0));
+        assert_eq!(next_move(&mp), Some(BotMove::Reveal(Position::new(2, 0, 0))));
+        let mut result = ClickResult::NothingHappened;
+        let mut moves = 0;
+        while let Some(bot_move) = next_move(&mp) {
+            result = play_move(&mut mp, bot_move);
+            moves += 1;
+        }
+        assert!(matches!(result, ClickResult::Win));
+        // a reveal, two flags, then chords or reveals for the corners
+        assert!(moves <= 5);
+    }
+    #[test]
+    fn test_play_games() {
+        let shape = BoardShape::rectangle(9, 9);
+        assert_eq!(play_games(shape.clone(), 10, &NoGuessGenerator, 20).unwrap(), 20);
+        assert!(play_games(shape, 10, &UniformGenerator, 50).unwrap() > 25);
+    }
+}
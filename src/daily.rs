@@ -0,0 +1,197 @@
+use std::fmt;
+use std::fs;
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+/// A calendar day, as days since 1970-01-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date(pub i64);
+
+/// Today's board for one level: everyone playing the same day and level
+/// gets the same mines.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyChallenge {
+    pub date: Date,
+    pub level: usize,
+}
+
+/// How a day's scored attempt at a level went.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DailyResult {
+    pub date: Date,
+    pub level: usize,
+    pub won: bool,
+    pub hints: usize,
+}
+
+/// Past daily results, one line each in a plain text file:
+/// `2021-03-01 level 0 won hints 0`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DailyHistory {
+    pub results: Vec<DailyResult>,
+}
+
+impl Date {
+    /// The current day in UTC, so the whole team changes boards at once.
+    pub fn today() -> Date {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        Date((secs / 86400) as i64)
+    }
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Date {
+        // days from civil, with years starting in March so leap days come last
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Date(era * 146097 + day_of_era - 719468)
+    }
+    pub fn ymd(self) -> (i64, u32, u32) {
+        let days = self.0 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = (if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 }) as u32;
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        (year, month, day)
+    }
+    /// Reads `YYYY-MM-DD`.
+    pub fn parse(text: &str) -> Result<Date, String> {
+        let parts: Vec<&str> = text.split('-').collect();
+        let numbers: Vec<i64> = parts.iter().filter_map(|part| part.parse().ok()).collect();
+        match numbers[..] {
+            [year, month, day] if parts.len() == 3 && (1..=12).contains(&month) && (1..=31).contains(&day) => {
+                let date = Date::from_ymd(year, month as u32, day as u32);
+                if date.ymd() != (year, month as u32, day as u32) {
+                    return Err(format!("no such day `{}`", text));
+                }
+                Ok(date)
+            }
+            _ => Err(format!("bad date `{}`", text)),
+        }
+    }
+}
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl DailyChallenge {
+    pub fn today(level: usize) -> DailyChallenge {
+        DailyChallenge { date: Date::today(), level }
+    }
+    /// The seed for `SeededGenerator`. Spelled out rather than hashed, the
+    /// standard hasher may change between Rust releases.
+    pub fn seed(&self) -> u64 {
+        // splitmix64
+        let mut z = (self.date.0 as u64).wrapping_mul(4).wrapping_add(self.level as u64).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl DailyHistory {
+    pub fn parse(text: &str) -> Result<DailyHistory, String> {
+        let mut results = vec![];
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let bad_line = || format!("bad daily result on line {}", number + 1);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [date, "level", level, outcome, "hints", hints] if outcome == "won" || outcome == "lost" => {
+                    results.push(DailyResult {
+                        date: Date::parse(date)?,
+                        level: level.parse().map_err(|_| bad_line())?,
+                        won: outcome == "won",
+                        hints: hints.parse().map_err(|_| bad_line())?,
+                    });
+                }
+                _ => return Err(bad_line()),
+            }
+        }
+        Ok(DailyHistory { results })
+    }
+    /// An empty history when the file doesn't exist yet.
+    pub fn load(path: &str) -> Result<DailyHistory, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => DailyHistory::parse(&text),
+            Err(_) => Ok(DailyHistory::default()),
+        }
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| e.to_string())
+    }
+    /// Whether the day's one scored attempt at the challenge is used up.
+    pub fn attempted(&self, challenge: &DailyChallenge) -> bool {
+        self.results.iter().any(|result| result.date == challenge.date && result.level == challenge.level)
+    }
+    pub fn record(&mut self, result: DailyResult) -> Result<(), String> {
+        if self.attempted(&DailyChallenge { date: result.date, level: result.level }) {
+            return Err(format!("{} level {} was already played", result.date, result.level));
+        }
+        self.results.push(result);
+        Ok(())
+    }
+    /// Days in a row the level was won, up to `today`, or up to yesterday
+    /// while today's board is still unplayed.
+    pub fn streak(&self, level: usize, today: Date) -> usize {
+        let won = |date: Date| self.results.iter().any(|result| result.date == date && result.level == level && result.won);
+        let played_today = self.attempted(&DailyChallenge { date: today, level });
+        let mut day = if played_today { today } else { Date(today.0 - 1) };
+        let mut streak = 0;
+        while won(day) {
+            streak += 1;
+            day = Date(day.0 - 1);
+        }
+        streak
+    }
+}
+impl fmt::Display for DailyHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for result in self.results.iter() {
+            let outcome = if result.won { "won" } else { "lost" };
+            writeln!(f, "{} level {} {} hints {}", result.date, result.level, outcome, result.hints)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_dates() {
+        assert_eq!(Date::from_ymd(1970, 1, 1), Date(0));
+        assert_eq!(Date::from_ymd(2000, 2, 29), Date(11016));
+        assert_eq!(Date(20744).ymd(), (2026, 10, 18));
+        assert_eq!(Date::parse("2026-10-18"), Ok(Date(20744)));
+        assert_eq!(Date(11016).to_string(), "2000-02-29");
+        assert!(Date::parse("2021-02-29").is_err());
+        assert!(Date::parse("2021-1").is_err());
+    }
+    #[test]
+    fn test_seeds() {
+        let challenge = DailyChallenge { date: Date(20744), level: 0 };
+        assert_eq!(challenge.seed(), DailyChallenge { date: Date(20744), level: 0 }.seed());
+        assert_ne!(challenge.seed(), DailyChallenge { date: Date(20744), level: 1 }.seed());
+        assert_ne!(challenge.seed(), DailyChallenge { date: Date(20745), level: 0 }.seed());
+    }
+    #[test]
+    fn test_history() {
+        let mut history = DailyHistory::parse("2026-10-16 level 0 won hints 0\n2026-10-17 level 0 won hints 2\n").unwrap();
+        let today = DailyChallenge { date: Date(20744), level: 0 };
+        assert!(!history.attempted(&today));
+        assert_eq!(history.streak(0, today.date), 2);
+        assert_eq!(history.streak(1, today.date), 0);
+        history.record(DailyResult { date: today.date, level: 0, won: false, hints: 0 }).unwrap();
+        assert!(history.attempted(&today));
+        assert!(history.record(DailyResult { date: today.date, level: 0, won: true, hints: 0 }).is_err());
+        assert_eq!(history.streak(0, today.date), 0);
+        assert_eq!(DailyHistory::parse(&history.to_string()), Ok(history));
+        assert!(DailyHistory::parse("2026-10-16 level x won hints 0").is_err());
+    }
+}
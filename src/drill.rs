@@ -0,0 +1,249 @@
+use std::fmt;
+use std::fs;
+use rand::{ seq::SliceRandom, Rng };
+use crate::puzzle::Puzzle;
+use crate::solver::{ Deduction, Knowledge };
+
+/// Drill boards are padded with holes up to the smallest board size.
+const DRILL_SIZE: usize = 5;
+
+/// The classic local situations the drills are made of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    OneOne,
+    OneTwo,
+    OneTwoOne,
+    OneTwoTwoOne,
+    Corner,
+}
+
+pub const PATTERNS: [Pattern; 5] = [Pattern::OneOne, Pattern::OneTwo, Pattern::OneTwoOne, Pattern::OneTwoTwoOne, Pattern::Corner];
+
+/// A pattern turned and mirrored at random, with the blocks its numbers prove
+/// safe or mined.
+#[derive(Debug, Clone)]
+pub struct Drill {
+    pub pattern: Pattern,
+    pub puzzle: Puzzle,
+    pub answer: Vec<Deduction>,
+}
+
+/// How the marks on a drill compare to its answer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrillScore {
+    pub right: usize,
+    /// marks on blocks that are the other way round or not certain at all
+    pub wrong: usize,
+    pub missed: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternStats {
+    pub pattern: Pattern,
+    pub tries: usize,
+    pub right: usize,
+    /// time spent on all the tries
+    pub seconds: f32,
+}
+
+/// Accuracy and reaction time per pattern over all sessions, one line each in
+/// a plain text file: `1-2-1 tries 12 right 10 seconds 34.5`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DrillStats {
+    pub patterns: Vec<PatternStats>,
+}
+
+impl Pattern {
+    pub fn name(self) -> &'static str {
+        match self {
+            Pattern::OneOne => "1-1",
+            Pattern::OneTwo => "1-2",
+            Pattern::OneTwoOne => "1-2-1",
+            Pattern::OneTwoTwoOne => "1-2-2-1",
+            Pattern::Corner => "corner",
+        }
+    }
+    pub fn from_name(name: &str) -> Result<Pattern, String> {
+        PATTERNS.iter().copied().find(|pattern| pattern.name() == name).ok_or(format!("unknown pattern `{}`", name))
+    }
+    /// The pattern along the top edge of the board, in the rows of
+    /// `Puzzle::from_rows`.
+    fn rows(self) -> &'static [&'static str] {
+        match self {
+            // the 1 against the edge has its mine next to it, the third block is safe
+            Pattern::OneOne => &["*--", "11."],
+            Pattern::OneTwo => &["*-*", "12."],
+            Pattern::OneTwoOne => &["-*-*-", ".121."],
+            Pattern::OneTwoTwoOne => &["--**--", ".1221."],
+            Pattern::Corner => &["*-*-", "-211", "-000"],
+        }
+    }
+}
+
+impl Drill {
+    /// `symmetry` picks one of the 8 ways to turn and mirror the pattern.
+    pub fn new(pattern: Pattern, symmetry: usize) -> Drill {
+        let rows = transformed(pattern.rows(), symmetry);
+        let rows: Vec<&str> = rows.iter().map(String::as_str).collect();
+        let puzzle = Puzzle::read_rows(pattern.name(), &rows).unwrap();
+        let answer = Knowledge::from_playground(&puzzle.playground().unwrap()).certain();
+        Drill { pattern, puzzle, answer }
+    }
+    pub fn random<R: Rng>(patterns: &[Pattern], rng: &mut R) -> Drill {
+        Drill::new(*patterns.choose(rng).unwrap(), rng.gen_range(0..8))
+    }
+    /// `marks` tells for every block whether it was marked as a mine, as safe,
+    /// or not at all.
+    pub fn score(&self, marks: &[Option<bool>]) -> DrillScore {
+        let mut score = DrillScore { right: 0, wrong: 0, missed: 0 };
+        for deduction in self.answer.iter() {
+            match marks[deduction.index] {
+                Some(is_mine) if is_mine == deduction.is_mine => score.right += 1,
+                Some(_) => score.wrong += 1,
+                None => score.missed += 1,
+            }
+        }
+        let unsure = (0..marks.len()).filter(|&index| marks[index].is_some() && self.answer.iter().all(|cur| cur.index != index));
+        score.wrong += unsure.count();
+        score
+    }
+}
+
+impl DrillScore {
+    pub fn is_perfect(&self) -> bool {
+        self.wrong == 0 && self.missed == 0
+    }
+}
+
+impl DrillStats {
+    pub fn parse(text: &str) -> Result<DrillStats, String> {
+        let mut patterns = vec![];
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let bad_line = || format!("bad drill stats on line {}", number + 1);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[..] {
+                [pattern, "tries", tries, "right", right, "seconds", seconds] => {
+                    patterns.push(PatternStats {
+                        pattern: Pattern::from_name(pattern)?,
+                        tries: tries.parse().map_err(|_| bad_line())?,
+                        right: right.parse().map_err(|_| bad_line())?,
+                        seconds: seconds.parse().map_err(|_| bad_line())?,
+                    });
+                }
+                _ => return Err(bad_line()),
+            }
+        }
+        Ok(DrillStats { patterns })
+    }
+    /// No stats when the file doesn't exist yet.
+    pub fn load(path: &str) -> Result<DrillStats, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => DrillStats::parse(&text),
+            Err(_) => Ok(DrillStats::default()),
+        }
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| e.to_string())
+    }
+    pub fn get(&self, pattern: Pattern) -> Option<&PatternStats> {
+        self.patterns.iter().find(|stats| stats.pattern == pattern)
+    }
+    pub fn record(&mut self, pattern: Pattern, score: &DrillScore, seconds: f32) {
+        let index = match self.patterns.iter().position(|stats| stats.pattern == pattern) {
+            Some(index) => index,
+            None => {
+                self.patterns.push(PatternStats { pattern, tries: 0, right: 0, seconds: 0.0 });
+                self.patterns.len() - 1
+            }
+        };
+        let stats = &mut self.patterns[index];
+        stats.tries += 1;
+        stats.right += score.is_perfect() as usize;
+        stats.seconds += seconds;
+    }
+}
+impl fmt::Display for DrillStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for stats in self.patterns.iter() {
+            writeln!(f, "{} tries {} right {} seconds {:.1}", stats.pattern.name(), stats.tries, stats.right, stats.seconds)?;
+        }
+        Ok(())
+    }
+}
+
+impl PatternStats {
+    pub fn accuracy(&self) -> f32 {
+        self.right as f32 / self.tries.max(1) as f32
+    }
+    pub fn average_seconds(&self) -> f32 {
+        self.seconds / self.tries.max(1) as f32
+    }
+}
+
+/// Mirrors the columns, then the rows, then swaps them, by the bits of
+/// `symmetry`, and pads the board with holes, the pattern in the middle.
+fn transformed(rows: &[&str], symmetry: usize) -> Vec<String> {
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut grid: Vec<Vec<char>> = rows.iter().map(|row| {
+        let mut cells: Vec<char> = row.chars().collect();
+        cells.resize(width, '.');
+        cells
+    }).collect();
+    if symmetry & 1 != 0 {
+        grid.iter_mut().for_each(|row| row.reverse());
+    }
+    if symmetry & 2 != 0 {
+        grid.reverse();
+    }
+    if symmetry & 4 != 0 {
+        grid = (0..width).map(|x| grid.iter().map(|row| row[x]).collect()).collect();
+    }
+    let (width, height) = (grid[0].len(), grid.len());
+    let (pad_x, pad_y) = (DRILL_SIZE.saturating_sub(width), DRILL_SIZE.saturating_sub(height));
+    let hole_row = ".".repeat(width + pad_x);
+    let mut padded = vec![hole_row.clone(); pad_y / 2];
+    for row in grid {
+        padded.push(format!("{}{}{}", ".".repeat(pad_x / 2), row.into_iter().collect::<String>(), ".".repeat(pad_x - pad_x / 2)));
+    }
+    padded.resize(padded.len() + pad_y - pad_y / 2, hole_row);
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_drills() {
+        for &pattern in PATTERNS.iter() {
+            let answers: Vec<usize> = (0..8).map(|symmetry| Drill::new(pattern, symmetry).answer.len()).collect();
+            assert!(answers.iter().all(|&count| count > 0 && count == answers[0]), "{}", pattern.name());
+        }
+        // safe, mine, safe, mine, safe along the top row
+        let drill = Drill::new(Pattern::OneTwoOne, 0);
+        let mut marks = vec![None; 25];
+        for deduction in drill.answer.iter() {
+            marks[deduction.index] = Some(deduction.is_mine);
+        }
+        assert_eq!(drill.answer.len(), 5);
+        assert!(drill.score(&marks).is_perfect());
+        let mine = drill.answer.iter().find(|deduction| deduction.is_mine).unwrap().index;
+        marks[mine] = None;
+        assert_eq!(drill.score(&marks), DrillScore { right: 4, wrong: 0, missed: 1 });
+        // only the third block of a 1-1 is certain
+        let drill = Drill::new(Pattern::OneOne, 0);
+        assert_eq!(drill.answer.len(), 1);
+        assert!(!drill.answer[0].is_mine);
+    }
+    #[test]
+    fn test_stats() {
+        let mut stats = DrillStats::parse("1-2-1 tries 3 right 2 seconds 9.0\n").unwrap();
+        let perfect = DrillScore { right: 5, wrong: 0, missed: 0 };
+        stats.record(Pattern::OneTwoOne, &perfect, 3.0);
+        stats.record(Pattern::Corner, &DrillScore { right: 2, wrong: 1, missed: 0 }, 4.0);
+        let one_two_one = stats.get(Pattern::OneTwoOne).unwrap();
+        assert_eq!((one_two_one.tries, one_two_one.accuracy(), one_two_one.average_seconds()), (4, 0.75, 3.0));
+        assert_eq!(stats.get(Pattern::Corner).unwrap().accuracy(), 0.0);
+        assert_eq!(DrillStats::parse(&stats.to_string()), Ok(stats));
+        assert!(DrillStats::parse("2-2 tries 1 right 1 seconds 1.0").is_err());
+    }
+}
@@ -1,20 +1,49 @@
-use std::cmp;
+use std::{cmp, collections::BTreeMap, fs};
 use bevy::{
     diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
     prelude::*,
+    window::WindowFocused,
 };
 use crate::mine_core::{ BlockType, BlockStatus, MinePlayground, MineBlock, Position, ClickResult };
+use crate::analysis;
+use crate::board_file::{ read_code, write_code, write_mbf, ReplayAction, ReplayEvent };
+use crate::daily::DailyChallenge;
+use crate::drill::{ Drill, Pattern };
+use crate::bot::{ self, BotMove };
+use crate::hint::{ self, HintKind };
+use crate::layout::{ self, FixedGenerator, LayoutGenerator, UniformGenerator };
+use crate::neighbourhood::Neighbourhood;
+use crate::puzzle::Puzzle;
+use crate::session::SessionMode;
+use crate::shape::BoardShape;
+use crate::topology::Topology;
+use crate::versus::MineRule;
 
-pub fn game_app(config: GameConfig) {
+mod daily;
+mod drill;
+mod editor;
+mod ghost;
+mod net;
+mod puzzle;
+mod replay;
+mod session;
+mod versus;
+
+pub fn game_app(mut config: GameConfig) {
+    let net_game = net::net_game(&mut config);
+    let (window_width, window_height) = window_size(&config);
     App::build()
         .add_resource(WindowDescriptor {
             vsync: false,
-            width: cmp::max(config.width * BLOCK_WIDTH, MIN_WIDTH) as f32,
-            height: cmp::max(config.height * BLOCK_WIDTH + Y_MARGIN, MIN_HEIGHT) as f32,
+            width: window_width,
+            height: window_height,
             title: String::from("Mine Sweeper"),
             resizable: false,
             ..Default::default()
         })
+        .add_resource(BotPlayer { active: false, elapsed: 0.0, played: false, allowed: true })
+        .add_resource(net_game)
+        .add_resource(layout_source_for(&config))
         .add_resource(config)
         .add_plugins(DefaultPlugins)
         .add_plugin(GamePlugin)
@@ -27,23 +56,54 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ButtonMaterials>()
             .add_resource(CursorLocation(Vec2::new(0.0, 0.0)))
+            .add_resource(ViewScroll::default())
+            .add_resource(ViewLayer::default())
+            .add_resource(HintState::default())
+            .add_resource(GameLog::default())
+            .add_resource(BlockTints::default())
+            .add_resource(ModeStatus::default())
+            .add_resource(GameResults::default())
+            .add_resource(CodeInput::default())
             .add_plugin(FrameTimeDiagnosticsPlugin)
             .add_resource(State::new(GameState::Prepare))
             .add_startup_system(setup.system())
             .add_system(fps_update.system())
-            .add_system(debug_text_update.system())
+            // after the modes, which tell their part of it during the frame
+            .add_system_to_stage(stage::POST_UPDATE, debug_text_update.system())
             .add_system(restart_button_system.system())
             .add_startup_system(new_map.system())
             .add_system(handle_movement.system())
             .add_system(handle_click.system())
+            .add_system(bot_system.system())
+            .add_system(hint_system.system())
+            .add_system_to_stage(stage::POST_UPDATE, tint_blocks.system())
+            .add_system(hint_text_update.system())
+            .add_system(pause_system.system())
+            .add_system(game_log_system.system())
+            // after the other systems, which ignore the keys typed into the field
+            .add_system_to_stage(stage::POST_UPDATE, code_input_system.system())
+            .add_system(export_board_system.system())
             .add_system(render_map.system())
+            .add_system(scroll_view.system())
+            .add_system(switch_layer.system())
             .add_stage_after(stage::UPDATE, STAGE, StateStage::<GameState>::default())
             .on_state_enter(STAGE, GameState::Prepare, init_map_render.system())
-            .on_state_enter(STAGE, GameState::Ready, new_map.system());
+            .on_state_enter(STAGE, GameState::Ready, new_map.system())
+            .add_plugin(replay::ReplayPlugin)
+            .add_plugin(daily::DailyPlugin)
+            .add_plugin(puzzle::PuzzlePlugin)
+            .add_plugin(editor::EditorPlugin)
+            .add_plugin(drill::DrillPlugin)
+            .add_plugin(versus::VersusPlugin)
+            .add_plugin(net::NetPlugin)
+            .add_plugin(ghost::GhostPlugin)
+            .add_plugin(session::SessionPlugin);
     }
 }
 
 const BLOCK_WIDTH: usize = 24;
+/// vertical distance between hex rows, relative to `BLOCK_WIDTH`
+const HEX_ROW_RATIO: f32 = 0.866;
 const MIN_HEIGHT: usize = 160;
 const MIN_WIDTH: usize = 160;
 const Y_MARGIN: usize = 50;
@@ -51,9 +111,22 @@ const SPRITE_SIZE: f32 = 48.0;
 const STAGE: &str = "game_state";
 const NEW_GAME_TEXT: &str = "New Game";
 const HIDDEN_INDEX: usize = 10;
+/// tips 9 to 26 only happen on layered boards, their sprites follow the flag
+const HIGH_TIP_INDEX: usize = 13;
+/// stacks of 2 and 3 mines, then of 2 and 3 flags
+const STACKED_MINE_INDEX: usize = 31;
+const STACKED_FLAG_INDEX: usize = 33;
+/// a mine hit while lives were left
+const EXPLODED_INDEX: usize = 35;
+const ATLAS_COLUMNS: usize = 36;
+/// how far, in pixels, the ghosts of the neighbouring layers are shifted
+const GHOST_SHIFT: f32 = 4.0;
+const EXPORT_FILE: &str = "exported_board.mbf";
 
 struct RefreshButton;
 struct DebugText;
+struct HintText;
+struct PauseText;
 struct MapData {
     map_entity: Entity,
 }
@@ -62,11 +135,175 @@ struct WindowOffset {
     x: f32,
     y: f32,
 }
-#[derive(Debug, Clone, Copy)]
+/// What the game is played as, one mode at a time. Every mode but `Classic`
+/// has a plugin of its own in the modules above.
+#[derive(Debug, Clone)]
+pub enum GameMode {
+    /// one generated board after another
+    Classic,
+    /// a recorded game played again on its board
+    Replay(Vec<ReplayEvent>),
+    /// today's shared board, `generator` is then seeded from the date
+    Daily(DailyChallenge),
+    /// the board of a code played instead of generated boards, see `load_code`
+    Code(String),
+    Puzzle(Puzzle),
+    /// the board being edited, named after the files it is saved to
+    Editor(Puzzle),
+    /// patterns to practise and the drill on the board, see `load_drills`
+    Drills(Vec<Pattern>, Drill),
+    /// two players take turns on every board, see `Versus`
+    Versus(MineRule),
+    /// the server address and the name to join it with, the board then comes
+    /// from the server, see `net`. Without a name the game is only watched.
+    Network(String, String),
+    /// boards played one after another against a clock, see `Session`
+    Session(SessionMode),
+}
+
+impl GameMode {
+    /// Whether the mode handles clicks on the board itself, rather than
+    /// `handle_click`.
+    fn own_clicks(&self) -> bool {
+        matches!(self, GameMode::Editor(_) | GameMode::Drills(..) | GameMode::Versus(_) | GameMode::Network(..))
+    }
+    /// Whether the bot and hints may help: not either versus player, nor
+    /// against the clock of a session, and network boards don't know their
+    /// mines.
+    fn allows_help(&self) -> bool {
+        !matches!(self, GameMode::Versus(_) | GameMode::Network(..) | GameMode::Session(_))
+    }
+    /// Whether a board code may replace the board, the editor, drills, network
+    /// games and sessions bring their own.
+    fn allows_codes(&self) -> bool {
+        !matches!(self, GameMode::Editor(_) | GameMode::Drills(..) | GameMode::Network(..) | GameMode::Session(_))
+    }
+    /// Whether `S` exports the board: the editor saves its own, and network
+    /// boards have no mines to export.
+    fn exports_board(&self) -> bool {
+        !matches!(self, GameMode::Editor(_) | GameMode::Network(..))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct GameConfig {
     pub width: usize,
     pub height: usize,
     pub mine_count: usize,
+    /// name of the layout generator, see `layout::by_name`
+    pub generator: String,
+    pub shape: BoardShape,
+    /// most mines a block may hold
+    pub max_stack: usize,
+    /// moves a second when watching the bot play
+    pub bot_speed: f32,
+    /// mines that can be hit before the game is lost
+    pub lives: usize,
+    pub mode: GameMode,
+    /// the port spectators can watch the game on
+    pub publish: Option<u16>,
+    /// a recorded game to race on the same board, see `Ghost`
+    pub ghost: Option<Vec<ReplayEvent>>,
+    /// where the fastest win on this seeded board is kept
+    pub best_replay: Option<String>,
+}
+
+impl GameConfig {
+    /// Plays the exact board of a code from now on, with its size and rules.
+    pub fn load_code(&mut self, code: &str) -> Result<(), String> {
+        let mp = read_code(code)?;
+        // tips above 26 have no sprites
+        if mp.shape.max_neighbours() * mp.max_stack > 26 {
+            return Err(String::from("code: too many mines around a block to draw"));
+        }
+        self.width = mp.width;
+        self.height = mp.height;
+        self.mine_count = mp.mine_counts().iter().sum();
+        self.shape = mp.shape.clone();
+        self.max_stack = mp.max_stack;
+        self.lives = mp.lives;
+        self.generator = String::from("code");
+        self.mode = GameMode::Code(code.trim().to_string());
+        self.ghost = None;
+        self.best_replay = None;
+        Ok(())
+    }
+    pub fn load_puzzle(&mut self, puzzle: Puzzle) {
+        self.fit_puzzle(&puzzle);
+        self.mode = GameMode::Puzzle(puzzle);
+    }
+    /// Practises `patterns` from now on, a random drill of them at a time.
+    pub fn load_drills(&mut self, patterns: Vec<Pattern>) {
+        let drill = Drill::random(&patterns, &mut rand::thread_rng());
+        self.fit_puzzle(&drill.puzzle);
+        self.mode = GameMode::Drills(patterns, drill);
+    }
+    fn fit_puzzle(&mut self, puzzle: &Puzzle) {
+        self.width = puzzle.shape.width;
+        self.height = puzzle.shape.height;
+        self.mine_count = puzzle.mine_count();
+        self.shape = puzzle.shape.clone();
+        self.max_stack = 1;
+        self.lives = 1;
+        self.generator = String::from("puzzle");
+        self.ghost = None;
+        self.best_replay = None;
+    }
+    /// The exact board to play, for codes, puzzles and drills, or the one
+    /// edited. Network boards start empty, the server fills them in.
+    fn preset_board(&self) -> Option<MinePlayground> {
+        match &self.mode {
+            GameMode::Code(code) => Some(read_code(code).unwrap()),
+            GameMode::Puzzle(puzzle) => Some(puzzle.playground().unwrap()),
+            GameMode::Drills(_, drill) => Some(drill.puzzle.playground().unwrap()),
+            GameMode::Editor(board) => Some(board.preview().unwrap()),
+            GameMode::Network(..) => Some(MinePlayground::from_layout(self.shape.clone(), &vec![false; self.shape.block_count()]).unwrap()),
+            _ => None,
+        }
+    }
+}
+
+struct LayoutSource(Box<dyn LayoutGenerator>);
+
+/// Preset boards are exact like fixed ones, so their generator only tells that
+/// no mine may be moved away from the first click.
+fn layout_source_for(config: &GameConfig) -> LayoutSource {
+    match config.preset_board() {
+        Some(mp) => LayoutSource(Box::new(FixedGenerator { width: mp.width, height: mp.height, layout: mp.mine_layout() })),
+        None => LayoutSource(layout::by_name(&config.generator).unwrap()),
+    }
+}
+
+/// The board code field, open while typing into it.
+#[derive(Default, Debug)]
+struct CodeInput {
+    active: bool,
+    text: String,
+}
+
+struct BotPlayer {
+    active: bool,
+    elapsed: f32,
+    /// whether the bot moved in the current game, its wins are not the player's
+    played: bool,
+    /// whether the bot may play at all, not in a scored daily game
+    allowed: bool,
+}
+
+/// The moves of the current game, for the analysis once it is over.
+#[derive(Default)]
+struct GameLog {
+    events: Vec<ReplayEvent>,
+    /// seconds since the first move
+    elapsed: f32,
+    /// blocks shown before the first move
+    start_shown: Vec<usize>,
+    report: Option<String>,
+}
+impl GameLog {
+    fn record(&mut self, action: ReplayAction, pos: Position) {
+        self.events.push(ReplayEvent { time: self.elapsed, action, pos });
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -74,12 +311,57 @@ enum GameState {
     Prepare,
     Ready,
     Running,
+    /// the board is hidden, resuming goes back to the state before
+    Paused,
     Over,
+    /// the board is changed rather than played, see `editor_system`
+    Editing,
+    /// a drill waits for its answer, see `drill_system`
+    Drilling,
 }
 
 #[derive(Default, Debug)]
 struct CursorLocation(Vec2);
+/// How many blocks the view is shifted by on boards that wrap around.
+#[derive(Default, Debug)]
+struct ViewScroll {
+    x: usize,
+    y: usize,
+}
+/// The layer shown on layered boards, and whether the layers right above and
+/// below show through it.
+#[derive(Default, Debug)]
+struct ViewLayer {
+    z: usize,
+    ghosts: bool,
+}
 struct LastActionText(String);
+/// Hints asked for in the current game, and the last one until the board
+/// changes. Games won with hints don't count for high scores.
+#[derive(Default, Debug)]
+struct HintState {
+    used: usize,
+    highlighted: Option<(Position, HintKind)>,
+    text: String,
+}
+/// Colours the modes give the blocks this frame, by `BoardShape::index`,
+/// white for the others.
+#[derive(Default)]
+struct BlockTints(Vec<Color>);
+impl BlockTints {
+    fn set(&mut self, index: usize, color: Color) {
+        if let Some(tint) = self.0.get_mut(index) {
+            *tint = color;
+        }
+    }
+}
+/// What the modes tell in the top left corner this frame, by label.
+#[derive(Default)]
+struct ModeStatus(BTreeMap<&'static str, String>);
+/// What the mode tells in the middle of the board once its game is over, who
+/// won a versus game or the results of a session.
+#[derive(Default)]
+struct GameResults(String);
 
 struct ButtonMaterials {
     normal: Handle<ColorMaterial>,
@@ -99,11 +381,14 @@ impl FromResources for ButtonMaterials {
 impl MineBlock {
     fn get_sprite_index(&self) -> usize {
         match self.bstatus {
-            BlockStatus::Flaged => 12,
+            BlockStatus::Flaged(count) if count > 1 => STACKED_FLAG_INDEX + count - 2,
+            BlockStatus::Flaged(_) => 12,
             BlockStatus::QuestionMarked => 11,
             BlockStatus::Shown => {
                 match self.btype {
-                    BlockType::Mine => 9,
+                    BlockType::Mine(count) if count > 1 => STACKED_MINE_INDEX + count - 2,
+                    BlockType::Mine(_) => 9,
+                    BlockType::Tip(val) if val > 8 => HIGH_TIP_INDEX + val - 9,
                     BlockType::Tip(val) => val,
                     BlockType::Space => 0,
                 }
@@ -173,6 +458,54 @@ fn setup(
             ..Default::default()
         })
         .with(FpsRefresh);
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Px(5.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: 16.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    alignment: TextAlignment::default(),
+                },
+            },
+            ..Default::default()
+        })
+        .with(HintText);
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                align_self: AlignSelf::FlexEnd,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px((window.height() as f32) / 2.0 - 20.0),
+                    left: Val::Px((window.width() as f32) / 2.0 - 40.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: String::new(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: 32.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    alignment: TextAlignment::default(),
+                },
+            },
+            ..Default::default()
+        })
+        .with(PauseText);
     commands.insert_resource(WindowOffset {
         x: window.width() as f32 / 2.0 - BLOCK_WIDTH as f32 / 2.0,
         y: window.height() as f32 / 2.0 - BLOCK_WIDTH as f32 / 2.0,
@@ -214,20 +547,60 @@ fn setup(
         });
 
     let texture_handle = asset_server.load("textures/block.png");
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(SPRITE_SIZE, SPRITE_SIZE), 13, 1);
+    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(SPRITE_SIZE, SPRITE_SIZE), ATLAS_COLUMNS, 1);
     let texture_atlas_handle = texture_atlases.add(texture_atlas);
     commands.insert_resource(texture_atlas_handle);
 }
+/// `pos` is where the sprite sits on screen, the block it shows also depends on
+/// `ViewScroll` and `ViewLayer`. Ghost sprites show the layer `layer_offset`
+/// away from the current one.
 struct RenderBlock {
     pos: Position,
+    layer_offset: i64,
 }
+/// Deals the next board. The modes keeping state about it start over once they
+/// see a new `MapData::map_entity`.
 fn new_map(
     commands: &mut Commands,
     config: Res<GameConfig>,
+    layout_source: Res<LayoutSource>,
+    mut hint_state: ResMut<HintState>,
+    mut game_log: ResMut<GameLog>,
+    mut game_results: ResMut<GameResults>,
+    mut bot_player: ResMut<BotPlayer>,
+    game_state: Res<State<GameState>>,
 ) {
-    commands
-        .insert_resource(MinePlayground::init(&config.width, &config.height, &config.mine_count).unwrap());
-    commands.spawn((MinePlayground::init(&config.width, &config.height, &config.mine_count).unwrap(), ));
+    // resuming a game paused before its first click keeps its board
+    if let Some(GameState::Paused) = game_state.previous() {
+        return;
+    }
+    *hint_state = HintState::default();
+    bot_player.played = false;
+    game_results.0.clear();
+    let generator = &*layout_source.0;
+    let mut start = generator.safe_start(&config.shape);
+    let mut mp = match config.preset_board() {
+        Some(mp) => mp,
+        None => match MinePlayground::generate_stacked(config.shape.clone(), &config.mine_count, config.max_stack, generator) {
+            Ok(mp) => mp,
+            // e.g. no guess-free layout found: a uniform board, without the
+            // opening the generator promised
+            Err(e) => {
+                hint_state.text = format!("{}, a uniform board is played instead", e);
+                start = None;
+                MinePlayground::generate_stacked(config.shape.clone(), &config.mine_count, config.max_stack, &UniformGenerator).unwrap()
+            }
+        },
+    };
+    mp.lives = config.lives;
+    if let Some(start) = start {
+        mp.click(&start);
+    }
+    *game_log = GameLog {
+        start_shown: (0..mp.shape.block_count()).filter(|&index| mp.block_at(index).bstatus == BlockStatus::Shown).collect(),
+        ..Default::default()
+    };
+    commands.spawn((mp, ));
     commands.insert_resource(MapData {
         map_entity: commands.current_entity().unwrap(),
     });
@@ -237,33 +610,62 @@ fn init_map_render(
     commands: &mut Commands,
     texture_atlases: Res<Assets<TextureAtlas>>,
     atlas_handle: Res<Handle<TextureAtlas>>,
-    window_offset: Res<WindowOffset>,
+    mut windows: ResMut<Windows>,
+    mut window_offset: ResMut<WindowOffset>,
+    mut centred: Query<(&mut Style, Option<&PauseText>), Or<(With<Button>, With<PauseText>)>>,
+    old_blocks: Query<Entity, With<RenderBlock>>,
     config: Res<GameConfig>,
     mut game_state: ResMut<State<GameState>>,
 ) {
-    println!("111init_map_render run once");
-    for y in 0..config.height {
-        for x in 0..config.width {
-            let texture_atlas = texture_atlases.get_handle(atlas_handle.clone());
-            commands
-                .spawn(SpriteSheetBundle {
-                    transform: Transform {
-                        translation: Vec3::new(
-                            (x * BLOCK_WIDTH) as f32 - window_offset.x,
-                            (y * BLOCK_WIDTH) as f32 - window_offset.y,
-                            0.0
-                        ),
-                        scale: Vec3::splat(0.5),
+    for entity in old_blocks.iter() {
+        commands.despawn(entity);
+    }
+    // a board loaded from a code may have another size
+    let (width, height) = window_size(&config);
+    let window = windows.get_primary_mut().unwrap();
+    if (window.width() as f32, window.height() as f32) != (width, height) {
+        window.set_resolution(width, height);
+        window_offset.x = width / 2.0 - BLOCK_WIDTH as f32 / 2.0;
+        window_offset.y = height / 2.0 - BLOCK_WIDTH as f32 / 2.0;
+        for (mut style, pause_text) in centred.iter_mut() {
+            let half_width = if pause_text.is_some() { 40.0 } else { 50.0 };
+            style.position.left = Val::Px(width / 2.0 - half_width);
+            if pause_text.is_some() {
+                style.position.top = Val::Px(height / 2.0 - 20.0);
+            }
+        }
+    }
+    let layer_offsets: &[i64] = if config.shape.depth > 1 { &[0, -1, 1] } else { &[0] };
+    for &layer_offset in layer_offsets {
+        let ghost_shift = Vec2::splat(layer_offset as f32 * GHOST_SHIFT);
+        for y in 0..config.height {
+            for x in 0..config.width {
+                let pos = Position::new(x, y, 0);
+                let texture_atlas = texture_atlases.get_handle(atlas_handle.clone());
+                commands
+                    .spawn(SpriteSheetBundle {
+                        transform: Transform {
+                            translation: (block_translation(x, y, config.shape.topology) + ghost_shift - Vec2::new(window_offset.x, window_offset.y))
+                                .extend(-(layer_offset.abs() as f32)),
+                            scale: Vec3::splat(0.5),
+                            ..Default::default()
+                        },
+                        texture_atlas,
+                        sprite: TextureAtlasSprite {
+                            index: HIDDEN_INDEX as u32,
+                            color: if layer_offset == 0 { Color::WHITE } else { Color::rgba(1.0, 1.0, 1.0, 0.3) },
+                        },
+                        // blocks cut out of the board are never drawn
+                        visible: Visible {
+                            is_visible: layer_offset == 0 && config.shape.is_playable(&pos),
+                            is_transparent: true,
+                        },
                         ..Default::default()
-                    },
-                    texture_atlas,
-                    sprite: TextureAtlasSprite::new(HIDDEN_INDEX as u32),
-                    ..Default::default()
-                })
-                .with(RenderBlock { pos: Position { x, y } });
+                    })
+                    .with(RenderBlock { pos, layer_offset });
+            }
         }
     }
-    println!("{:?}", game_state.current());
     game_state.set_next(GameState::Ready).unwrap();
 }
 fn render_map (
@@ -273,15 +675,94 @@ fn render_map (
         // filters
         Changed<MinePlayground>, 
     >,
-    mut sprites: Query<(&mut TextureAtlasSprite, &RenderBlock)>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mut hint_state: ResMut<HintState>,
+    mut sprites: Query<(&mut TextureAtlasSprite, &mut Visible, &RenderBlock)>,
 ) {
     for mp in query.iter() {
-        println!("detect mp changed{:?}", mp.shown_count);
-        for (mut sprite, rb) in sprites.iter_mut() {
-            sprite.index = mp.map[rb.pos.y][rb.pos.x].get_sprite_index() as u32;
-
-            // println!("x:{:?}-y:{:?}-block:{:?}-index:{:?}", rb.pos.x, rb.pos.y, mp.map[rb.pos.y][rb.pos.x], mp.map[rb.pos.y][rb.pos.x].get_sprite_index() as u32);
-        }
+        hint_state.highlighted = None;
+        draw_blocks(mp, &scroll, &view_layer, &mut sprites);
+    }
+}
+fn draw_blocks(
+    mp: &MinePlayground,
+    scroll: &ViewScroll,
+    view_layer: &ViewLayer,
+    sprites: &mut Query<(&mut TextureAtlasSprite, &mut Visible, &RenderBlock)>,
+) {
+    for (mut sprite, mut visible, rb) in sprites.iter_mut() {
+        let pos = match sprite_block(rb, scroll, view_layer, &mp.shape) {
+            Some(pos) if rb.layer_offset == 0 || view_layer.ghosts => pos,
+            _ => {
+                visible.is_visible = false;
+                continue;
+            }
+        };
+        sprite.index = if mp.exploded.contains(&pos) {
+            EXPLODED_INDEX as u32
+        } else {
+            mp.block(&pos).get_sprite_index() as u32
+        };
+        visible.is_visible = mp.shape.is_playable(&pos);
+    }
+}
+/// The block a sprite shows, `None` for ghosts beyond the top or bottom layer.
+fn sprite_block(rb: &RenderBlock, scroll: &ViewScroll, view_layer: &ViewLayer, shape: &BoardShape) -> Option<Position> {
+    let z = view_layer.z as i64 + rb.layer_offset;
+    if z < 0 || z as usize >= shape.depth {
+        return None;
+    }
+    Some(Position::new((rb.pos.x + scroll.x) % shape.width, (rb.pos.y + scroll.y) % shape.height, z as usize))
+}
+/// Arrow keys move the view around boards without edges.
+fn scroll_view(
+    keys: Res<Input<KeyCode>>,
+    config: Res<GameConfig>,
+    mut scroll: ResMut<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    mut sprites: Query<(&mut TextureAtlasSprite, &mut Visible, &RenderBlock)>,
+    game_state: Res<State<GameState>>,
+) {
+    if !config.shape.topology.wraps() || *game_state.current() == GameState::Paused {
+        return;
+    }
+    let (width, height) = (config.width, config.height);
+    let (x, y) = (scroll.x, scroll.y);
+    if keys.just_pressed(KeyCode::Left) { scroll.x = (x + width - 1) % width; }
+    if keys.just_pressed(KeyCode::Right) { scroll.x = (x + 1) % width; }
+    if keys.just_pressed(KeyCode::Down) { scroll.y = (y + height - 1) % height; }
+    if keys.just_pressed(KeyCode::Up) { scroll.y = (y + 1) % height; }
+    if (x, y) != (scroll.x, scroll.y) {
+        let mp = mquery.get(map_data.map_entity).unwrap();
+        draw_blocks(mp, &scroll, &view_layer, &mut sprites);
+    }
+}
+/// Page up and down move between the layers of a layered board, G toggles the
+/// ghosts of the layers right above and below.
+fn switch_layer(
+    keys: Res<Input<KeyCode>>,
+    config: Res<GameConfig>,
+    scroll: Res<ViewScroll>,
+    mut view_layer: ResMut<ViewLayer>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    mut sprites: Query<(&mut TextureAtlasSprite, &mut Visible, &RenderBlock)>,
+    code_input: Res<CodeInput>,
+    game_state: Res<State<GameState>>,
+) {
+    if config.shape.depth < 2 || *game_state.current() == GameState::Paused || code_input.active {
+        return;
+    }
+    let (z, ghosts) = (view_layer.z, view_layer.ghosts);
+    if keys.just_pressed(KeyCode::PageUp) && z + 1 < config.shape.depth { view_layer.z = z + 1; }
+    if keys.just_pressed(KeyCode::PageDown) && z > 0 { view_layer.z = z - 1; }
+    if keys.just_pressed(KeyCode::G) { view_layer.ghosts = !ghosts; }
+    if (z, ghosts) != (view_layer.z, view_layer.ghosts) {
+        let mp = mquery.get(map_data.map_entity).unwrap();
+        draw_blocks(mp, &scroll, &view_layer, &mut sprites);
     }
 }
 fn handle_movement(
@@ -298,57 +779,406 @@ fn handle_click(
     btns: Res<Input<MouseButton>>,
     cursor_pos: Res<CursorLocation>,
     config: Res<GameConfig>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    layout_source: Res<LayoutSource>,
     mut mquery: Query<&mut MinePlayground>,
     map_data: Res<MapData>,
     mut text_query: Query<&mut Text, With<RefreshButton>>,
     mut last_action_text: ResMut<LastActionText>,
+    mut game_log: ResMut<GameLog>,
     mut game_state: ResMut<State<GameState>>,
 ) {
-    if let GameState::Over = game_state.current() {
+    match game_state.current() {
+        GameState::Ready | GameState::Running => {},
+        _ => return,
+    }
+    if config.mode.own_clicks() {
         return;
     }
     if btns.just_released(MouseButton::Left) {
-        if let Some((x, y)) = get_block_index_by_cursor_pos(cursor_pos.0, *config) {
-            println!("{:?}-{:?}", x, y);
+        if let Some(pos) = get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer) {
+            game_log.record(ReplayAction::Reveal, pos);
             let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
-            // some little fix here
-            if let GameState::Ready = game_state.current()  {
-                if let BlockType::Mine = mp.map[y][x].btype {
-                    mp.fix(&x, &y);
-                }
-            }
-            let click_result = mp.click(&x, &y);
-            println!("{:?}", click_result);
-            match click_result {
-                ClickResult::Wasted => {
-                    let mut text = text_query.iter_mut().next().unwrap();
-                    text.value = String::from("Game Over");
-                    *last_action_text = LastActionText(String::from("Game Over"));
-                    game_state.set_next(GameState::Over).unwrap();
-                    return;
-                },
-                ClickResult::Win => {
-                    let mut text = text_query.iter_mut().next().unwrap();
-                    text.value = String::from("Finished!");
-                    *last_action_text = LastActionText(String::from("Finished!"));
-                    game_state.set_next(GameState::Over).unwrap();
-                    return;
-                }
-                _ => {}
+            if let Some(result_text) = reveal_block(&mut mp, &pos, &*layout_source.0, &mut game_state) {
+                show_result(&mut text_query, &mut last_action_text, result_text);
+                return;
             }
+        }
+    }
+    if btns.just_released(MouseButton::Right) {
+        if let Some(pos) = get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer) {
             if let GameState::Ready = game_state.current()  {
                 game_state.set_next(GameState::Running).unwrap();
             }
+            game_log.record(ReplayAction::Flag, pos);
+            let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+            mp.right_click(&pos);
         }
     }
-    if btns.just_released(MouseButton::Right) {
-        if let Some((x, y)) = get_block_index_by_cursor_pos(cursor_pos.0, *config) {
-            println!("{:?}-{:?}", x, y);
+    if btns.just_released(MouseButton::Middle) {
+        if let Some(pos) = get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer) {
+            game_log.record(ReplayAction::Chord, pos);
+            let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+            if let Some(result_text) = chord_block(&mut mp, &pos, &mut game_state) {
+                show_result(&mut text_query, &mut last_action_text, result_text);
+            }
+        }
+    }
+}
+
+/// Reveals a block like a left click does. Returns the button text to show when
+/// the click ends the game.
+fn reveal_block(
+    mp: &mut MinePlayground,
+    pos: &Position,
+    generator: &dyn LayoutGenerator,
+    game_state: &mut State<GameState>,
+) -> Option<&'static str> {
+    // some little fix here
+    if let GameState::Ready = game_state.current()  {
+        if mp.block(pos).btype.mine_count() > 0 && generator.allows_first_click_fix() {
+            mp.fix(pos);
+        }
+    }
+    finish_click(mp.click(pos), game_state)
+}
+
+/// Opens around a number like a middle click does.
+fn chord_block(mp: &mut MinePlayground, pos: &Position, game_state: &mut State<GameState>) -> Option<&'static str> {
+    finish_click(mp.chord(pos), game_state)
+}
+
+fn finish_click(click_result: ClickResult, game_state: &mut State<GameState>) -> Option<&'static str> {
+    match click_result {
+        ClickResult::Wasted => {
+            game_state.set_next(GameState::Over).unwrap();
+            return Some("Game Over");
+        },
+        ClickResult::Win => {
+            game_state.set_next(GameState::Over).unwrap();
+            return Some("Finished!");
+        }
+        _ => {}
+    }
+    if let GameState::Ready = game_state.current()  {
+        game_state.set_next(GameState::Running).unwrap();
+    }
+    None
+}
+
+fn show_result(
+    text_query: &mut Query<&mut Text, With<RefreshButton>>,
+    last_action_text: &mut LastActionText,
+    result_text: &str,
+) {
+    let mut text = text_query.iter_mut().next().unwrap();
+    text.value = String::from(result_text);
+    *last_action_text = LastActionText(String::from(result_text));
+}
+
+/// `B` lets the bot play one move, `A` starts or stops it playing on its own at
+/// `bot_speed` moves a second, one move a frame at most.
+fn bot_system(
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    layout_source: Res<LayoutSource>,
+    mut bot_player: ResMut<BotPlayer>,
+    code_input: Res<CodeInput>,
+    mut mquery: Query<&mut MinePlayground>,
+    map_data: Res<MapData>,
+    mut text_query: Query<&mut Text, With<RefreshButton>>,
+    mut last_action_text: ResMut<LastActionText>,
+    mut game_log: ResMut<GameLog>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if keys.just_released(KeyCode::A) && !code_input.active {
+        bot_player.active = !bot_player.active;
+        bot_player.elapsed = 0.0;
+    }
+    let mut play = keys.just_released(KeyCode::B) && !code_input.active;
+    if bot_player.active {
+        let interval = 1.0 / config.bot_speed;
+        bot_player.elapsed += time.delta_seconds();
+        if bot_player.elapsed >= interval {
+            bot_player.elapsed = (bot_player.elapsed - interval).min(interval);
+            play = true;
+        }
+    }
+    match game_state.current() {
+        GameState::Ready | GameState::Running => {},
+        _ => return,
+    }
+    if !play || !bot_player.allowed || !config.mode.allows_help() {
+        return;
+    }
+    let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+    let bot_move = match bot::next_move(&mp) {
+        Some(bot_move) => bot_move,
+        None => {
+            bot_player.active = false;
+            return;
+        }
+    };
+    bot_player.played = true;
+    let result_text = match bot_move {
+        BotMove::Reveal(pos) => {
+            game_log.record(ReplayAction::Reveal, pos);
+            reveal_block(&mut mp, &pos, &*layout_source.0, &mut game_state)
+        }
+        BotMove::Flag(pos) => {
             if let GameState::Ready = game_state.current()  {
                 game_state.set_next(GameState::Running).unwrap();
             }
-            let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
-            mp.right_click(&x, &y);
+            game_log.record(ReplayAction::Flag, pos);
+            mp.right_click(&pos);
+            None
+        }
+        BotMove::Chord(pos) => {
+            game_log.record(ReplayAction::Chord, pos);
+            chord_block(&mut mp, &pos, &mut game_state)
+        }
+    };
+    if let Some(result_text) = result_text {
+        show_result(&mut text_query, &mut last_action_text, result_text);
+        bot_player.active = false;
+    }
+}
+
+/// `H` highlights a block the numbers prove safe or a mine, or the least risky
+/// one when there is none, and tells why.
+fn hint_system(
+    keys: Res<Input<KeyCode>>,
+    config: Res<GameConfig>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    mut hint_state: ResMut<HintState>,
+    code_input: Res<CodeInput>,
+    game_state: Res<State<GameState>>,
+) {
+    match game_state.current() {
+        GameState::Ready | GameState::Running => {},
+        _ => return,
+    }
+    if !keys.just_released(KeyCode::H) || code_input.active || !config.mode.allows_help() {
+        return;
+    }
+    let mp = mquery.get(map_data.map_entity).unwrap();
+    match hint::hint(mp) {
+        Some(found) => {
+            hint_state.used += 1;
+            hint_state.highlighted = Some((found.pos, found.kind));
+            hint_state.text = found.text;
+            if config.shape.depth > 1 {
+                hint_state.text += &format!(" (layer {})", found.pos.z + 1);
+            }
+        }
+        None => hint_state.text = String::from("no hints with stacked mines"),
+    }
+}
+
+/// Tints the blocks as the modes asked this frame, and the block of the hint
+/// over them, then clears the tints for the next frame.
+fn tint_blocks(
+    hint_state: Res<HintState>,
+    config: Res<GameConfig>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mut tints: ResMut<BlockTints>,
+    mut sprites: Query<(&mut TextureAtlasSprite, &RenderBlock)>,
+) {
+    for (mut sprite, rb) in sprites.iter_mut() {
+        if rb.layer_offset != 0 {
+            continue;
+        }
+        let block = sprite_block(rb, &scroll, &view_layer, &config.shape);
+        let color = match (hint_state.highlighted, block) {
+            (Some((pos, kind)), Some(block)) if block == pos => match kind {
+                HintKind::Safe => Color::rgb(0.5, 1.0, 0.5),
+                HintKind::Mine => Color::rgb(1.0, 0.5, 0.5),
+                HintKind::Guess => Color::rgb(1.0, 1.0, 0.5),
+            },
+            (_, Some(block)) => tints.0.get(config.shape.index(&block)).copied().unwrap_or(Color::WHITE),
+            _ => Color::WHITE,
+        };
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+    tints.0 = vec![Color::WHITE; config.shape.block_count()];
+}
+
+fn hint_text_update(
+    mut query: Query<&mut Text, With<HintText>>,
+    hint_state: Res<HintState>,
+    code_input: Res<CodeInput>,
+    game_log: Res<GameLog>,
+    last_action_text: Res<LastActionText>,
+) {
+    for mut text in query.iter_mut() {
+        text.value = if code_input.active {
+            format!("board code: {}_", code_input.text)
+        } else if let Some(report) = &game_log.report {
+            report.clone()
+        } else if last_action_text.0 == "Finished!" && hint_state.used > 0 {
+            format!("won with {} hints, no high score", hint_state.used)
+        } else if hint_state.used > 0 {
+            format!("{} [hints: {}]", hint_state.text, hint_state.used)
+        } else {
+            hint_state.text.clone()
+        };
+    }
+}
+
+/// `P` or `Escape` pauses and resumes, and the game pauses when the window
+/// loses focus. The board is hidden meanwhile, so a pause can't be used to
+/// think, and the bot, replay and session clocks stop with it. The same text
+/// shows the `GameResults` of the mode once its game is over.
+fn pause_system(
+    keys: Res<Input<KeyCode>>,
+    focus_events: Res<Events<WindowFocused>>,
+    mut focus_reader: Local<EventReader<WindowFocused>>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    mut sprites: Query<(&mut TextureAtlasSprite, &mut Visible, &RenderBlock)>,
+    mut text_query: Query<&mut Text, With<PauseText>>,
+    code_input: Res<CodeInput>,
+    game_results: Res<GameResults>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let lost_focus = focus_reader.iter(&focus_events).any(|ev| !ev.focused);
+    let toggle = (keys.just_released(KeyCode::P) || keys.just_released(KeyCode::Escape)) && !code_input.active;
+    let mut paused = *game_state.current() == GameState::Paused;
+    match game_state.current() {
+        GameState::Ready | GameState::Running if toggle || lost_focus => {
+            game_state.set_next(GameState::Paused).unwrap();
+            for (_, mut visible, _) in sprites.iter_mut() {
+                visible.is_visible = false;
+            }
+            paused = true;
+        }
+        GameState::Paused if toggle => {
+            let resume = game_state.previous().cloned().unwrap_or(GameState::Running);
+            game_state.set_next(resume).unwrap();
+            let mp = mquery.get(map_data.map_entity).unwrap();
+            draw_blocks(mp, &scroll, &view_layer, &mut sprites);
+            paused = false;
+        }
+        _ => {}
+    }
+    // New Game leaves the pause too
+    if game_state.next() == Some(&GameState::Prepare) {
+        paused = false;
+    }
+    let results = match game_state.current() {
+        GameState::Over if game_state.next().is_none() => game_results.0.clone(),
+        _ => String::new(),
+    };
+    for mut text in text_query.iter_mut() {
+        text.value = if paused { String::from("Paused") } else { results.clone() };
+    }
+}
+
+/// Runs the clock of the moves, and once the game is over plays them again
+/// against the solver: the timeline shows in the bottom left corner, every
+/// move in the console.
+fn game_log_system(
+    time: Res<Time>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    mut game_log: ResMut<GameLog>,
+    game_state: Res<State<GameState>>,
+) {
+    match game_state.current() {
+        GameState::Running => game_log.elapsed += time.delta_seconds(),
+        GameState::Over if game_log.report.is_none() && !game_log.events.is_empty() => {
+            let mp = mquery.get(map_data.map_entity).unwrap();
+            game_log.report = Some(match analysis::analyse(mp, &game_log.start_shown, &game_log.events) {
+                Ok(reports) => {
+                    for report in reports.iter() {
+                        println!("{}", report);
+                    }
+                    format!("{} {}", analysis::timeline(&reports), analysis::summary(&reports))
+                }
+                Err(e) => e,
+            });
+        }
+        _ => {}
+    }
+}
+
+/// `C` opens a field to type a board code into, `Enter` loads it and `Escape`
+/// closes it.
+fn code_input_system(
+    keys: Res<Input<KeyCode>>,
+    char_events: Res<Events<ReceivedCharacter>>,
+    mut char_reader: Local<EventReader<ReceivedCharacter>>,
+    mut code_input: ResMut<CodeInput>,
+    mut config: ResMut<GameConfig>,
+    mut layout_source: ResMut<LayoutSource>,
+    mut hint_state: ResMut<HintState>,
+    mut scroll: ResMut<ViewScroll>,
+    mut view_layer: ResMut<ViewLayer>,
+    mut text_query: Query<&mut Text, With<RefreshButton>>,
+    mut last_action_text: ResMut<LastActionText>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let typed: Vec<char> = char_reader.iter(&char_events).map(|ev| ev.char).collect();
+    // spectators can't follow a board of another size
+    if !config.mode.allows_codes() || config.publish.is_some() {
+        return;
+    }
+    if !code_input.active {
+        if keys.just_released(KeyCode::C) {
+            code_input.active = true;
+            code_input.text.clear();
+        }
+        return;
+    }
+    code_input.text.extend(typed.into_iter().filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_'));
+    if keys.just_pressed(KeyCode::Back) {
+        code_input.text.pop();
+    }
+    if keys.just_released(KeyCode::Escape) {
+        code_input.active = false;
+    }
+    if !keys.just_released(KeyCode::Return) {
+        return;
+    }
+    code_input.active = false;
+    if let Err(e) = config.load_code(&code_input.text) {
+        hint_state.text = e;
+        return;
+    }
+    *layout_source = layout_source_for(&config);
+    *scroll = ViewScroll::default();
+    *view_layer = ViewLayer::default();
+    show_result(&mut text_query, &mut last_action_text, NEW_GAME_TEXT);
+    if *game_state.current() != GameState::Prepare {
+        game_state.set_next(GameState::Prepare).unwrap();
+    }
+}
+
+/// `S` writes the board to `EXPORT_FILE` and prints its code.
+fn export_board_system(
+    keys: Res<Input<KeyCode>>,
+    config: Res<GameConfig>,
+    code_input: Res<CodeInput>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+) {
+    if keys.just_released(KeyCode::S) && !code_input.active && config.mode.exports_board() {
+        let mp = mquery.get(map_data.map_entity).unwrap();
+        match write_code(mp) {
+            Ok(code) => println!("board code: {}", code),
+            Err(e) => println!("no board code: {}", e),
+        }
+        match write_mbf(mp).and_then(|bytes| fs::write(EXPORT_FILE, bytes).map_err(|e| e.to_string())) {
+            Ok(_) => println!("board exported to {}", EXPORT_FILE),
+            Err(e) => println!("export failed: {}", e),
         }
     }
 }
@@ -371,13 +1201,34 @@ fn fps_update(
         );
     }
 }
+/// The state, what the modes tell of their game, then the board options that
+/// change how it plays.
 fn debug_text_update(
     mut query: Query<&mut Text, With<DebugText>>,
+    config: Res<GameConfig>,
+    view_layer: Res<ViewLayer>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    mut mode_status: ResMut<ModeStatus>,
     game_state: Res<State<GameState>>,
 ) {
     for mut text in query.iter_mut() {
         text.value = format!("state: {:?}", game_state.current());
+        for (label, status) in mode_status.0.iter() {
+            text.value += &format!(" {}: {}", label, status);
+        }
+        if config.lives > 1 {
+            let mp = mquery.get(map_data.map_entity).unwrap();
+            text.value += &format!(" lives: {}/{}", mp.lives, config.lives);
+        }
+        if config.shape.depth > 1 {
+            text.value += &format!(" layer: {}/{}", view_layer.z + 1, config.shape.depth);
+        }
+        if config.shape.neighbourhood != Neighbourhood::Classic {
+            text.value += &format!(" neighbours: {}", config.shape.neighbourhood.name());
+        }
     }
+    mode_status.0.clear();
 }
 
 fn restart_button_system(
@@ -413,11 +1264,63 @@ fn restart_button_system(
     }
 }
 
-fn get_block_index_by_cursor_pos(pos: Vec2, config: GameConfig) -> Option<(usize, usize)> {
+fn window_size(config: &GameConfig) -> (f32, f32) {
+    let (board_width, board_height) = board_pixel_size(config);
+    (cmp::max(board_width, MIN_WIDTH) as f32, cmp::max(board_height + Y_MARGIN, MIN_HEIGHT) as f32)
+}
+
+fn board_pixel_size(config: &GameConfig) -> (usize, usize) {
+    match config.shape.topology {
+        Topology::Hex => (
+            config.width * BLOCK_WIDTH + BLOCK_WIDTH / 2,
+            ((config.height - 1) as f32 * BLOCK_WIDTH as f32 * HEX_ROW_RATIO) as usize + BLOCK_WIDTH,
+        ),
+        _ => (config.width * BLOCK_WIDTH, config.height * BLOCK_WIDTH),
+    }
+}
+
+/// Centre of a block, relative to the centre of block (0, 0).
+fn block_translation(x: usize, y: usize, topology: Topology) -> Vec2 {
+    let width = BLOCK_WIDTH as f32;
+    match topology {
+        Topology::Hex => Vec2::new(x as f32 * width + (y % 2) as f32 * width / 2.0, y as f32 * width * HEX_ROW_RATIO),
+        _ => Vec2::new(x as f32 * width, y as f32 * width),
+    }
+}
+
+/// Picks the hexagon under the cursor: pixel to axial coordinates, cube
+/// rounding, then back to rows with odd ones shifted right.
+fn get_hex_index_by_cursor_pos(pos: Vec2, config: &GameConfig) -> Option<(usize, usize)> {
+    let size = BLOCK_WIDTH as f32 / 3f32.sqrt();
+    let (px, py) = (pos.x - BLOCK_WIDTH as f32 / 2.0, pos.y - BLOCK_WIDTH as f32 / 2.0);
+    let q = (3f32.sqrt() / 3.0 * px - py / 3.0) / size;
+    let r = (2.0 / 3.0 * py) / size;
+    let (cube_x, cube_z) = (q, r);
+    let cube_y = -cube_x - cube_z;
+    let (mut rx, ry, mut rz) = (cube_x.round(), cube_y.round(), cube_z.round());
+    let (dx, dy, dz) = ((rx - cube_x).abs(), (ry - cube_y).abs(), (rz - cube_z).abs());
+    if dx > dy && dx > dz {
+        rx = -ry - rz;
+    } else if dz > dy {
+        rz = -rx - ry;
+    }
+    let (q, r) = (rx as i64, rz as i64);
+    let (x, y) = (q + (r - (r & 1)) / 2, r);
+    if x < 0 || y < 0 || x as usize >= config.width || y as usize >= config.height {
+        return None;
+    }
+    Some((x as usize, y as usize))
+}
+
+/// The block under the cursor, on the layer being shown.
+fn get_block_index_by_cursor_pos(pos: Vec2, config: &GameConfig, scroll: &ViewScroll, view_layer: &ViewLayer) -> Option<Position> {
+    if let Topology::Hex = config.shape.topology {
+        return get_hex_index_by_cursor_pos(pos, config).map(|(x, y)| Position::new(x, y, view_layer.z));
+    }
     let x = (pos.x / BLOCK_WIDTH as f32).floor() as usize;
     let y = (pos.y / BLOCK_WIDTH as f32).floor() as usize;
     if (0..config.height).contains(&y) && (0..config.width).contains(&x) {
-        return Some((x, y));
+        return Some(Position::new((x + scroll.x) % config.width, (y + scroll.y) % config.height, view_layer.z));
     }
     None
 }
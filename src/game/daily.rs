@@ -0,0 +1,92 @@
+use bevy::prelude::*;
+use crate::daily::{ DailyHistory, DailyResult };
+use super::{ BotPlayer, GameConfig, GameMode, GameState, HintState, LastActionText, ModeStatus };
+
+const DAILY_FILE: &str = "daily_history.txt";
+
+/// Scores the first game of today's board and keeps the history of them.
+pub(super) struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<DailyState>()
+            .add_system(daily_system.system())
+            .add_system(daily_status.system());
+    }
+}
+
+/// Only the first game of a daily challenge each day is scored. It is saved
+/// as lost on its first move and updated when it ends, so giving up doesn't
+/// bring another try.
+struct DailyState {
+    history: DailyHistory,
+    scored: bool,
+    /// index in `history` of the attempt being played
+    entry: Option<usize>,
+}
+impl FromResources for DailyState {
+    fn from_resources(resources: &Resources) -> Self {
+        let config = resources.get::<GameConfig>().unwrap();
+        let (history, scored) = match &config.mode {
+            GameMode::Daily(challenge) => {
+                let history = DailyHistory::load(DAILY_FILE).unwrap_or_else(|e| panic!("{}: {}", DAILY_FILE, e));
+                let scored = !history.attempted(challenge);
+                (history, scored)
+            }
+            _ => (DailyHistory::default(), false),
+        };
+        DailyState { history, scored, entry: None }
+    }
+}
+
+/// Saves the scored game, which the bot sits out.
+fn daily_system(
+    config: Res<GameConfig>,
+    hint_state: Res<HintState>,
+    last_action_text: Res<LastActionText>,
+    mut daily_state: ResMut<DailyState>,
+    mut bot_player: ResMut<BotPlayer>,
+    game_state: Res<State<GameState>>,
+) {
+    // a board code loaded leaves the daily game behind
+    let scored = daily_state.scored && matches!(config.mode, GameMode::Daily(_));
+    if bot_player.allowed == scored {
+        bot_player.allowed = !scored;
+    }
+    let challenge = match &config.mode {
+        GameMode::Daily(challenge) if scored => *challenge,
+        _ => return,
+    };
+    let daily_state = &mut *daily_state;
+    match (game_state.current(), daily_state.entry) {
+        (GameState::Running, None) => {
+            let result = DailyResult { date: challenge.date, level: challenge.level, won: false, hints: 0 };
+            daily_state.history.record(result).unwrap();
+            daily_state.entry = Some(daily_state.history.results.len() - 1);
+        }
+        (GameState::Over, Some(entry)) => {
+            let result = &mut daily_state.history.results[entry];
+            result.won = last_action_text.0 == "Finished!";
+            result.hints = hint_state.used;
+            daily_state.scored = false;
+        }
+        // a new game before the end, the attempt stays lost
+        (GameState::Prepare, Some(_)) | (GameState::Ready, Some(_)) => daily_state.scored = false,
+        _ => return,
+    }
+    if let Err(e) = daily_state.history.save(DAILY_FILE) {
+        println!("saving {} failed: {}", DAILY_FILE, e);
+    }
+}
+
+fn daily_status(config: Res<GameConfig>, daily_state: Res<DailyState>, mut mode_status: ResMut<ModeStatus>) {
+    let challenge = match &config.mode {
+        GameMode::Daily(challenge) => challenge,
+        _ => return,
+    };
+    let mut status = format!("{} streak: {}", challenge.date, daily_state.history.streak(challenge.level, challenge.date));
+    if !daily_state.scored {
+        status += " (practice)";
+    }
+    mode_status.0.insert("daily", status);
+}
@@ -0,0 +1,145 @@
+use bevy::prelude::*;
+use crate::drill::DrillStats;
+use crate::mine_core::MinePlayground;
+use super::{
+    get_block_index_by_cursor_pos, show_result, BlockTints, CursorLocation, GameConfig, GameMode, GameState, HintState, LastActionText,
+    MapData, ModeStatus, RefreshButton, ViewLayer, ViewScroll, NEW_GAME_TEXT,
+};
+
+const DRILL_STATS_FILE: &str = "drill_stats.txt";
+
+/// Pattern drills checked against the solver, see `drill_system`.
+pub(super) struct DrillPlugin;
+
+impl Plugin for DrillPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<DrillState>()
+            .add_system(drill_system.system())
+            .add_system(drill_tint.system())
+            .add_system(drill_status.system());
+    }
+}
+
+/// The marks on the drill, `Some(true)` for mines, and the time taken until
+/// `Enter`.
+struct DrillState {
+    marks: Vec<Option<bool>>,
+    elapsed: f32,
+    stats: DrillStats,
+}
+impl FromResources for DrillState {
+    fn from_resources(resources: &Resources) -> Self {
+        let config = resources.get::<GameConfig>().unwrap();
+        let stats = match config.mode {
+            GameMode::Drills(..) => DrillStats::load(DRILL_STATS_FILE).unwrap_or_else(|e| panic!("{}: {}", DRILL_STATS_FILE, e)),
+            _ => DrillStats::default(),
+        };
+        DrillState { marks: vec![], elapsed: 0.0, stats }
+    }
+}
+
+/// In drills a left click marks a block safe and a right click marks a mine,
+/// clicking again removes the mark. `Enter` checks the marks against the
+/// solver, then brings the next drill.
+fn drill_system(
+    btns: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    cursor_pos: Res<CursorLocation>,
+    mut config: ResMut<GameConfig>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mut mquery: Query<&mut MinePlayground>,
+    map_data: Res<MapData>,
+    mut drill_state: ResMut<DrillState>,
+    mut hint_state: ResMut<HintState>,
+    mut text_query: Query<&mut Text, With<RefreshButton>>,
+    mut last_action_text: ResMut<LastActionText>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let (patterns, drill) = match &config.mode {
+        GameMode::Drills(patterns, drill) => (patterns, drill),
+        _ => return,
+    };
+    let drill_state = &mut *drill_state;
+    match game_state.current() {
+        GameState::Ready => {
+            drill_state.marks = vec![None; config.shape.block_count()];
+            drill_state.elapsed = 0.0;
+            hint_state.text = String::from("mark the safe blocks and the mines, then press Enter");
+            game_state.set_next(GameState::Drilling).unwrap();
+        }
+        GameState::Drilling => {
+            drill_state.elapsed += time.delta_seconds();
+            let index = get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer)
+                .map(|pos| config.shape.index(&pos))
+                .filter(|&index| config.shape.mask[index] && !drill.puzzle.shown[index]);
+            let mark = match index {
+                Some(index) if btns.just_released(MouseButton::Left) => Some((index, false)),
+                Some(index) if btns.just_released(MouseButton::Right) => Some((index, true)),
+                _ => None,
+            };
+            if let Some((index, is_mine)) = mark {
+                let marks = &mut drill_state.marks;
+                marks[index] = if marks[index] == Some(is_mine) { None } else { Some(is_mine) };
+                let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+                *mp = drill.puzzle.playground().unwrap();
+                for index in (0..marks.len()).filter(|&index| marks[index] == Some(true)) {
+                    mp.right_click(&config.shape.position(index));
+                }
+            }
+            if keys.just_released(KeyCode::Return) {
+                let score = drill.score(&drill_state.marks);
+                drill_state.stats.record(drill.pattern, &score, drill_state.elapsed);
+                if let Err(e) = drill_state.stats.save(DRILL_STATS_FILE) {
+                    println!("saving {} failed: {}", DRILL_STATS_FILE, e);
+                }
+                hint_state.text = if score.is_perfect() {
+                    format!("right in {:.1}s, Enter for the next one", drill_state.elapsed)
+                } else {
+                    format!("{} right, {} wrong, {} missed, Enter for the next one", score.right, score.wrong, score.missed)
+                };
+                show_result(&mut text_query, &mut last_action_text, if score.is_perfect() { "Right!" } else { "Wrong" });
+                game_state.set_next(GameState::Over).unwrap();
+            }
+        }
+        GameState::Over if keys.just_released(KeyCode::Return) => {
+            let patterns = patterns.clone();
+            config.load_drills(patterns);
+            show_result(&mut text_query, &mut last_action_text, NEW_GAME_TEXT);
+            game_state.set_next(GameState::Prepare).unwrap();
+        }
+        _ => {}
+    }
+}
+
+/// Blocks marked safe are green while answering, then the answer shows:
+/// green for safe blocks, red for mines.
+fn drill_tint(config: Res<GameConfig>, drill_state: Res<DrillState>, mut tints: ResMut<BlockTints>, game_state: Res<State<GameState>>) {
+    let drill = match &config.mode {
+        GameMode::Drills(_, drill) => drill,
+        _ => return,
+    };
+    let (safe, mine) = (Color::rgb(0.5, 1.0, 0.5), Color::rgb(1.0, 0.5, 0.5));
+    if *game_state.current() == GameState::Over {
+        for deduction in drill.answer.iter() {
+            tints.set(deduction.index, if deduction.is_mine { mine } else { safe });
+        }
+    } else {
+        for index in (0..drill_state.marks.len()).filter(|&index| drill_state.marks[index] == Some(false)) {
+            tints.set(index, safe);
+        }
+    }
+}
+
+fn drill_status(config: Res<GameConfig>, drill_state: Res<DrillState>, mut mode_status: ResMut<ModeStatus>) {
+    let drill = match &config.mode {
+        GameMode::Drills(_, drill) => drill,
+        _ => return,
+    };
+    let mut status = drill.pattern.name().to_string();
+    if let Some(stats) = drill_state.stats.get(drill.pattern) {
+        status += &format!(" right: {:.0}% in {:.1}s", stats.accuracy() * 100.0, stats.average_seconds());
+    }
+    mode_status.0.insert("drill", status);
+}
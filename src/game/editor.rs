@@ -0,0 +1,113 @@
+use std::fs;
+use bevy::prelude::*;
+use crate::board_file::{ write_code, write_mbf };
+use crate::mine_core::MinePlayground;
+use crate::puzzle::Puzzle;
+use super::{ get_block_index_by_cursor_pos, BlockTints, CursorLocation, GameConfig, GameMode, GameState, HintState, MapData, ModeStatus, ViewLayer, ViewScroll };
+
+/// The level editor, see `editor_system`.
+pub(super) struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(editor_system.system())
+            .add_system(editor_tint.system())
+            .add_system(editor_status.system());
+    }
+}
+
+/// In the editor a left click puts or removes a mine, a right click picks
+/// whether a safe block starts revealed, and `V` asks the solver whether the
+/// board can be finished without guessing. `S` saves it.
+fn editor_system(
+    btns: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    cursor_pos: Res<CursorLocation>,
+    mut config: ResMut<GameConfig>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mut mquery: Query<&mut MinePlayground>,
+    map_data: Res<MapData>,
+    mut hint_state: ResMut<HintState>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    if !matches!(config.mode, GameMode::Editor(_)) {
+        return;
+    }
+    match game_state.current() {
+        GameState::Ready => {
+            game_state.set_next(GameState::Editing).unwrap();
+            return;
+        }
+        GameState::Editing => {}
+        _ => return,
+    }
+    let index = get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer).map(|pos| config.shape.index(&pos));
+    let board = match &mut config.mode {
+        GameMode::Editor(board) => board,
+        _ => return,
+    };
+    let edited = match index {
+        Some(index) if btns.just_released(MouseButton::Left) => board.toggle_mine(index),
+        Some(index) if btns.just_released(MouseButton::Right) => board.toggle_shown(index),
+        _ => false,
+    };
+    if edited {
+        let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+        *mp = board.preview().unwrap();
+        hint_state.text.clear();
+    }
+    if keys.just_released(KeyCode::V) {
+        hint_state.text = match board.check() {
+            Ok(_) => String::from("solvable without guessing"),
+            Err(e) => e,
+        };
+    }
+    if keys.just_released(KeyCode::S) {
+        hint_state.text = save_edited_board(board);
+    }
+}
+
+/// Blocks that don't start revealed are greyed out in the editor.
+fn editor_tint(config: Res<GameConfig>, mut tints: ResMut<BlockTints>, game_state: Res<State<GameState>>) {
+    let board = match &config.mode {
+        GameMode::Editor(board) if *game_state.current() == GameState::Editing => board,
+        _ => return,
+    };
+    for index in (0..board.layout.len()).filter(|&index| !board.shown[index] && !board.layout[index]) {
+        tints.set(index, Color::rgb(0.6, 0.6, 0.6));
+    }
+}
+
+fn editor_status(config: Res<GameConfig>, mut mode_status: ResMut<ModeStatus>) {
+    if let GameMode::Editor(board) = &config.mode {
+        mode_status.0.insert("editing", format!("{} mines: {}", board.name, board.mine_count()));
+    }
+}
+
+/// Writes the edited board to `NAME.mbf` and as a puzzle to `NAME.txt`, each
+/// when the board fits the format.
+fn save_edited_board(board: &Puzzle) -> String {
+    let mbf_path = format!("{}.mbf", board.name);
+    let puzzle_path = format!("{}.txt", board.name);
+    let mut saved = vec![];
+    let mp = board.playground().unwrap();
+    match write_mbf(&mp).and_then(|bytes| fs::write(&mbf_path, bytes).map_err(|e| e.to_string())) {
+        Ok(_) => saved.push(mbf_path),
+        Err(e) => println!("saving {} failed: {}", mbf_path, e),
+    }
+    let entry = board.to_rows().map(|rows| format!("name: {}\n{}\n", board.name, rows.join("\n")));
+    match entry.and_then(|entry| fs::write(&puzzle_path, entry).map_err(|e| e.to_string())) {
+        Ok(_) => saved.push(puzzle_path),
+        Err(e) => println!("saving {} failed: {}", puzzle_path, e),
+    }
+    match write_code(&mp) {
+        Ok(code) => println!("board code: {}", code),
+        Err(e) => println!("no board code: {}", e),
+    }
+    if saved.is_empty() {
+        String::from("nothing saved, see the console")
+    } else {
+        format!("saved {}", saved.join(" and "))
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+use bevy::prelude::*;
+use crate::board_file::{ read_rawvf, write_rawvf, Replay };
+use crate::ghost::Ghost;
+use crate::mine_core::MinePlayground;
+use super::{ BlockTints, BotPlayer, GameConfig, GameLog, GameState, HintState, LastActionText, MapData, ModeStatus };
+
+/// Races a recorded game on the same board, and keeps the fastest win on a
+/// seeded board to race later.
+pub(super) struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(GhostRace::default())
+            .add_system(ghost_system.system())
+            .add_system(ghost_tint.system())
+            .add_system(ghost_status.system())
+            .add_system(best_replay_system.system());
+    }
+}
+
+/// The ghost raced on the current board.
+#[derive(Default)]
+struct GhostRace {
+    ghost: Option<Ghost>,
+    /// the board raced on, a new one starts the race over
+    board: Option<Entity>,
+    /// whether the game was checked for a new best replay
+    checked: bool,
+}
+
+/// Starts the ghost from the blocks a new board shows.
+fn ghost_system(
+    config: Res<GameConfig>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    mut ghost_race: ResMut<GhostRace>,
+) {
+    if ghost_race.board == Some(map_data.map_entity) {
+        return;
+    }
+    let mp = mquery.get(map_data.map_entity).unwrap();
+    *ghost_race = GhostRace {
+        ghost: config.ghost.as_ref().map(|events| Ghost::new(mp, events).unwrap()),
+        board: Some(map_data.map_entity),
+        checked: false,
+    };
+}
+
+/// Blocks the ghost has opened are tinted, its last click brighter.
+fn ghost_tint(config: Res<GameConfig>, ghost_race: Res<GhostRace>, game_log: Res<GameLog>, mut tints: ResMut<BlockTints>) {
+    let ghost = match &ghost_race.ghost {
+        Some(ghost) => ghost,
+        None => return,
+    };
+    for index in (0..config.shape.block_count()).filter(|&index| ghost.opened(index, game_log.elapsed)) {
+        tints.set(index, Color::rgb(0.8, 0.87, 1.0));
+    }
+    if let Some(pos) = ghost.cursor(game_log.elapsed) {
+        tints.set(config.shape.index(&pos), Color::rgb(0.5, 0.6, 1.0));
+    }
+}
+
+fn ghost_status(
+    ghost_race: Res<GhostRace>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    game_log: Res<GameLog>,
+    mut mode_status: ResMut<ModeStatus>,
+) {
+    let ghost = match &ghost_race.ghost {
+        Some(ghost) => ghost,
+        None => return,
+    };
+    let mp = mquery.get(map_data.map_entity).unwrap();
+    let status = match ghost.delta(mp.shown_count, game_log.elapsed) {
+        Some(delta) if delta > 0.0 => format!("{:.1}s behind", delta),
+        Some(delta) => format!("{:.1}s ahead", -delta),
+        None => String::from("passed"),
+    };
+    mode_status.0.insert("ghost", status);
+}
+
+/// Keeps the replay of a win on a seeded board when it is the fastest yet,
+/// the ghost of `--ghost best`. Wins with hints or bot moves don't count.
+fn best_replay_system(
+    config: Res<GameConfig>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    game_log: Res<GameLog>,
+    hint_state: Res<HintState>,
+    bot_player: Res<BotPlayer>,
+    last_action_text: Res<LastActionText>,
+    mut ghost_race: ResMut<GhostRace>,
+    game_state: Res<State<GameState>>,
+) {
+    let path = match &config.best_replay {
+        Some(path) if *game_state.current() == GameState::Over && !ghost_race.checked => path,
+        _ => return,
+    };
+    ghost_race.checked = true;
+    if last_action_text.0 != "Finished!" || hint_state.used > 0 || bot_player.played {
+        return;
+    }
+    let mp = mquery.get(map_data.map_entity).unwrap();
+    let replay = Replay { width: mp.width, height: mp.height, layout: mp.mine_layout(), events: game_log.events.clone() };
+    let best = fs::read_to_string(path).ok().and_then(|text| read_rawvf(&text).ok());
+    if best.map_or(true, |best| replay.duration() < best.duration()) {
+        match fs::write(path, write_rawvf(&replay)) {
+            Ok(_) => println!("best time {:.2}s, replay saved to {}", replay.duration(), path),
+            Err(e) => println!("saving {} failed: {}", path, e),
+        }
+    }
+}
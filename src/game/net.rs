@@ -0,0 +1,278 @@
+use bevy::prelude::*;
+use crate::board_file::ReplayAction;
+use crate::mine_core::{ BlockStatus, BlockType, MinePlayground, Position };
+use crate::net::{ self, Client, ClientMessage, NetMode, Publisher, ServerMessage };
+use crate::shape::BoardShape;
+use super::{
+    get_block_index_by_cursor_pos, show_result, BlockTints, CursorLocation, GameConfig, GameLog, GameMode, GameState, HintState, LastActionText,
+    MapData, ModeStatus, RefreshButton, ViewLayer, ViewScroll, NEW_GAME_TEXT,
+};
+
+/// Network games played on a server, and spectators watching the game here.
+pub(super) struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Spectators>()
+            .add_system(net_system.system())
+            .add_system(net_tint.system())
+            .add_system(net_status.system())
+            .add_system(publish_system.system());
+    }
+}
+
+/// The connection of a network game and what the server told of the board,
+/// the players and their progress.
+pub(super) struct NetGame {
+    client: Option<Client>,
+    /// every block as last heard, by `BoardShape::index`
+    view: Vec<(BlockType, BlockStatus)>,
+    /// the board shown, a new one gets the blocks heard so far
+    board: Option<Entity>,
+    players: Vec<String>,
+    /// safe blocks opened and to open by every player, in races
+    progress: Vec<(usize, usize)>,
+    results: Vec<Option<bool>>,
+    /// a New Game then asks the server for a new board
+    over: bool,
+    /// the block under the mouse of the player watched
+    cursor: Option<Position>,
+}
+impl NetGame {
+    /// The board as heard, without mines of its own.
+    fn mirror(&self, shape: &BoardShape) -> MinePlayground {
+        let mut mp = MinePlayground::from_layout(shape.clone(), &vec![false; shape.block_count()]).unwrap();
+        for (index, &(btype, bstatus)) in self.view.iter().enumerate() {
+            mp.set_block(&shape.position(index), btype, bstatus);
+        }
+        mp
+    }
+}
+
+/// Joins the server of a network game, whose board size then replaces the
+/// one of `config`.
+pub(super) fn net_game(config: &mut GameConfig) -> NetGame {
+    let client = match &config.mode {
+        GameMode::Network(address, name) => {
+            let client = if name.is_empty() { Client::watch(address) } else { Client::connect(address, name) };
+            Some(client.unwrap_or_else(|e| panic!("{}", e)))
+        }
+        _ => None,
+    };
+    if let Some(client) = &client {
+        let welcome = client.welcome;
+        config.width = welcome.width;
+        config.height = welcome.height;
+        config.mine_count = welcome.mine_count;
+        config.shape = BoardShape::rectangle(welcome.width, welcome.height);
+        config.max_stack = 1;
+        config.lives = 1;
+    }
+    let view = vec![(BlockType::Space, BlockStatus::Hidden); config.shape.block_count()];
+    NetGame { client, view, board: None, players: vec![], progress: vec![], results: vec![], over: false, cursor: None }
+}
+
+/// In network games clicks go to the server, and the board shows what it
+/// sends back. A New Game once the board ended deals a new one to everyone.
+/// Spectators only get the board, with the moves in the bottom left corner.
+fn net_system(
+    btns: Res<Input<MouseButton>>,
+    cursor_pos: Res<CursorLocation>,
+    config: Res<GameConfig>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mut mquery: Query<&mut MinePlayground>,
+    map_data: Res<MapData>,
+    mut net_game: ResMut<NetGame>,
+    mut hint_state: ResMut<HintState>,
+    mut text_query: Query<&mut Text, With<RefreshButton>>,
+    mut last_action_text: ResMut<LastActionText>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let net_game = &mut *net_game;
+    let client = match &net_game.client {
+        Some(client) => client,
+        None => return,
+    };
+    let mut sent = vec![];
+    let playing = client.welcome.mode != NetMode::Spectate && matches!(game_state.current(), GameState::Ready | GameState::Running);
+    if playing {
+        if net_game.over {
+            sent.push(ClientMessage::New);
+            net_game.over = false;
+        }
+        if let Some(pos) = get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer) {
+            if btns.just_released(MouseButton::Left) {
+                sent.push(ClientMessage::Reveal(pos));
+            } else if btns.just_released(MouseButton::Right) {
+                sent.push(ClientMessage::Flag(pos));
+            } else if btns.just_released(MouseButton::Middle) {
+                sent.push(ClientMessage::Chord(pos));
+            }
+        }
+    }
+    for message in sent.iter() {
+        if let Err(e) = client.send(message) {
+            hint_state.text = e;
+        }
+    }
+    if sent.iter().any(|message| *message != ClientMessage::New) && *game_state.current() == GameState::Ready {
+        game_state.set_next(GameState::Running).unwrap();
+    }
+    let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+    if net_game.board != Some(map_data.map_entity) {
+        net_game.board = Some(map_data.map_entity);
+        *mp = net_game.mirror(&config.shape);
+    }
+    for message in client.poll() {
+        match message {
+            ServerMessage::Board => {
+                net_game.view = vec![(BlockType::Space, BlockStatus::Hidden); config.shape.block_count()];
+                net_game.results.iter_mut().for_each(|result| *result = None);
+                net_game.over = false;
+                if *game_state.current() == GameState::Over && game_state.next().is_none() {
+                    show_result(&mut text_query, &mut last_action_text, NEW_GAME_TEXT);
+                    game_state.set_next(GameState::Prepare).unwrap();
+                }
+                *mp = net_game.mirror(&config.shape);
+            }
+            ServerMessage::Block { pos, btype, bstatus } => {
+                net_game.view[config.shape.index(&pos)] = (btype, bstatus);
+                mp.set_block(&pos, btype, bstatus);
+            }
+            ServerMessage::Player { id, name } => {
+                if net_game.players.len() <= id {
+                    net_game.players.resize(id + 1, String::new());
+                    net_game.progress.resize(id + 1, (0, 0));
+                    net_game.results.resize(id + 1, None);
+                }
+                net_game.players[id] = name;
+            }
+            ServerMessage::Progress { id, shown, safe } if id < net_game.progress.len() => net_game.progress[id] = (shown, safe),
+            ServerMessage::Result { id, won } => {
+                if let Some(result) = net_game.results.get_mut(id) {
+                    *result = Some(won);
+                }
+                let mine = client.welcome.mode != NetMode::Race || id == client.welcome.id;
+                if mine && game_state.next().is_none() {
+                    net_game.over = true;
+                    show_result(&mut text_query, &mut last_action_text, if won { "Finished!" } else { "Game Over" });
+                    game_state.set_next(GameState::Over).unwrap();
+                }
+            }
+            ServerMessage::Move(message) => hint_state.text = message.to_string(),
+            ServerMessage::Cursor(pos) => net_game.cursor = Some(pos),
+            ServerMessage::Error(text) => hint_state.text = text,
+            _ => {}
+        }
+    }
+}
+
+/// The block under the mouse of the player watched is lit up, a ghost of
+/// their cursor.
+fn net_tint(config: Res<GameConfig>, net_game: Res<NetGame>, mut tints: ResMut<BlockTints>) {
+    if let Some(pos) = net_game.cursor {
+        tints.set(config.shape.index(&pos), Color::rgb(1.0, 1.0, 0.6));
+    }
+}
+
+/// The players of the game, with their progress in races.
+fn net_status(net_game: Res<NetGame>, mut mode_status: ResMut<ModeStatus>) {
+    let mode = match &net_game.client {
+        Some(client) => client.welcome.mode,
+        None => return,
+    };
+    let players: Vec<String> = net_game.players.iter().enumerate()
+        .map(|(id, name)| match (mode, net_game.results.get(id), net_game.progress.get(id)) {
+            (NetMode::Race, Some(Some(won)), _) => format!("{} {}", name, if *won { "won" } else { "lost" }),
+            (NetMode::Race, _, Some(&(shown, safe))) => format!("{} {}", name, progress_bar(shown, safe)),
+            _ => name.clone(),
+        })
+        .collect();
+    mode_status.0.insert(mode.name(), players.join(" "));
+}
+
+/// `[###-------]`, ten characters for the share of safe blocks opened.
+fn progress_bar(shown: usize, safe: usize) -> String {
+    let filled = shown * 10 / safe.max(1);
+    format!("[{}{}]", "#".repeat(filled), "-".repeat(10 - filled))
+}
+
+/// The game as spectators last heard it, to send them only what changed.
+struct Spectators {
+    publisher: Option<Publisher>,
+    /// the board published, a new one is sent whole
+    board: Option<Entity>,
+    statuses: Vec<BlockStatus>,
+    /// moves of `GameLog` already sent
+    moves_sent: usize,
+    cursor: Option<Position>,
+    result_sent: bool,
+}
+impl FromResources for Spectators {
+    fn from_resources(resources: &Resources) -> Self {
+        let config = resources.get::<GameConfig>().unwrap();
+        let publisher = config.publish.map(|port| {
+            let publisher = Publisher::listen(port).unwrap_or_else(|e| panic!("{}", e));
+            println!("spectators can watch on 127.0.0.1:{}", port);
+            publisher
+        });
+        Spectators { publisher, board: None, statuses: vec![], moves_sent: 0, cursor: None, result_sent: false }
+    }
+}
+
+/// Sends spectators the blocks that changed since the last frame, the moves,
+/// the cursor and the result.
+fn publish_system(
+    config: Res<GameConfig>,
+    cursor_pos: Res<CursorLocation>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    mquery: Query<&MinePlayground>,
+    map_data: Res<MapData>,
+    game_log: Res<GameLog>,
+    last_action_text: Res<LastActionText>,
+    mut spectators: ResMut<Spectators>,
+    game_state: Res<State<GameState>>,
+) {
+    let spectators = &mut *spectators;
+    let publisher = match &spectators.publisher {
+        Some(publisher) => publisher,
+        None => return,
+    };
+    let mp = mquery.get(map_data.map_entity).unwrap();
+    publisher.welcome(mp, config.mine_count);
+    let mut messages = vec![];
+    if spectators.board != Some(map_data.map_entity) {
+        spectators.board = Some(map_data.map_entity);
+        spectators.statuses = vec![BlockStatus::Hidden; mp.shape.block_count()];
+        spectators.moves_sent = 0;
+        spectators.result_sent = false;
+        messages.push(ServerMessage::Board);
+    }
+    for index in 0..spectators.statuses.len() {
+        let bstatus = mp.block_at(index).bstatus;
+        if bstatus != spectators.statuses[index] {
+            spectators.statuses[index] = bstatus;
+            messages.push(net::block_message(mp, index));
+        }
+    }
+    for event in game_log.events.iter().skip(spectators.moves_sent) {
+        messages.push(ServerMessage::Move(match event.action {
+            ReplayAction::Reveal => ClientMessage::Reveal(event.pos),
+            ReplayAction::Flag => ClientMessage::Flag(event.pos),
+            ReplayAction::Chord => ClientMessage::Chord(event.pos),
+        }));
+    }
+    spectators.moves_sent = game_log.events.len();
+    let cursor = get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer);
+    if cursor.is_some() && cursor != spectators.cursor {
+        messages.push(ServerMessage::Cursor(cursor.unwrap()));
+    }
+    spectators.cursor = cursor;
+    if *game_state.current() == GameState::Over && !spectators.result_sent {
+        spectators.result_sent = true;
+        messages.push(ServerMessage::Result { id: 0, won: last_action_text.0 == "Finished!" });
+    }
+    publisher.publish(&messages);
+}
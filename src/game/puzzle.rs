@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+use crate::puzzle::PuzzleProgress;
+use super::{ BotPlayer, GameConfig, GameMode, LastActionText, ModeStatus };
+
+const PUZZLE_PROGRESS_FILE: &str = "puzzle_progress.txt";
+
+/// Keeps which puzzles of the pack were solved.
+pub(super) struct PuzzlePlugin;
+
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(PuzzleProgress::load(PUZZLE_PROGRESS_FILE))
+            .add_system(puzzle_system.system())
+            .add_system(puzzle_status.system());
+    }
+}
+
+/// Marks the puzzle solved once it is won, by the player rather than the bot.
+fn puzzle_system(
+    config: Res<GameConfig>,
+    bot_player: Res<BotPlayer>,
+    last_action_text: Res<LastActionText>,
+    mut progress: ResMut<PuzzleProgress>,
+) {
+    let puzzle = match &config.mode {
+        GameMode::Puzzle(puzzle) if last_action_text.0 == "Finished!" && !bot_player.played && !progress.is_solved(&puzzle.name) => puzzle,
+        _ => return,
+    };
+    progress.mark_solved(&puzzle.name);
+    if let Err(e) = progress.save(PUZZLE_PROGRESS_FILE) {
+        println!("saving {} failed: {}", PUZZLE_PROGRESS_FILE, e);
+    }
+}
+
+fn puzzle_status(config: Res<GameConfig>, progress: Res<PuzzleProgress>, mut mode_status: ResMut<ModeStatus>) {
+    let puzzle = match &config.mode {
+        GameMode::Puzzle(puzzle) => puzzle,
+        _ => return,
+    };
+    let mut status = format!("{} mines: {}", puzzle.name, puzzle.mine_count());
+    if progress.is_solved(&puzzle.name) {
+        status += " (solved)";
+    }
+    mode_status.0.insert("puzzle", status);
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use crate::board_file::ReplayAction;
+use crate::mine_core::MinePlayground;
+use super::{ chord_block, reveal_block, show_result, GameConfig, GameLog, GameMode, GameState, LastActionText, LayoutSource, MapData, RefreshButton };
+
+/// Plays a recorded game again on its board, move by move.
+pub(super) struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(ReplayPlayer::default())
+            .add_system(replay_system.system());
+    }
+}
+
+#[derive(Default)]
+struct ReplayPlayer {
+    /// the board replayed, a new one is replayed from the start
+    board: Option<Entity>,
+    next: usize,
+    elapsed: f32,
+}
+
+fn replay_system(
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    layout_source: Res<LayoutSource>,
+    mut replay_player: ResMut<ReplayPlayer>,
+    mut mquery: Query<&mut MinePlayground>,
+    map_data: Res<MapData>,
+    mut text_query: Query<&mut Text, With<RefreshButton>>,
+    mut last_action_text: ResMut<LastActionText>,
+    mut game_log: ResMut<GameLog>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let events = match &config.mode {
+        GameMode::Replay(events) => events,
+        _ => return,
+    };
+    if replay_player.board != Some(map_data.map_entity) {
+        *replay_player = ReplayPlayer { board: Some(map_data.map_entity), next: 0, elapsed: 0.0 };
+    }
+    match game_state.current() {
+        GameState::Ready | GameState::Running => {},
+        _ => return,
+    }
+    if replay_player.next >= events.len() {
+        return;
+    }
+    replay_player.elapsed += time.delta_seconds();
+    let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+    while replay_player.next < events.len() && events[replay_player.next].time <= replay_player.elapsed {
+        let event = events[replay_player.next];
+        replay_player.next += 1;
+        game_log.record(event.action, event.pos);
+        let result_text = match event.action {
+            ReplayAction::Reveal => reveal_block(&mut mp, &event.pos, &*layout_source.0, &mut game_state),
+            ReplayAction::Flag => {
+                mp.right_click(&event.pos);
+                None
+            }
+            ReplayAction::Chord => chord_block(&mut mp, &event.pos, &mut game_state),
+        };
+        if let Some(result_text) = result_text {
+            show_result(&mut text_query, &mut last_action_text, result_text);
+            return;
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use bevy::prelude::*;
+use crate::session::Session;
+use super::{ show_result, GameConfig, GameLog, GameMode, GameResults, GameState, LastActionText, ModeStatus, RefreshButton, NEW_GAME_TEXT, STAGE };
+
+/// Time attack and survival, boards one after another against a clock.
+pub(super) struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SessionState>()
+            .add_system(session_system.system())
+            .add_system(session_status.system())
+            .on_state_exit(STAGE, GameState::Over, next_board.system());
+    }
+}
+
+/// The session the boards belong to, each board over is counted once.
+struct SessionState {
+    session: Option<Session>,
+    /// mines of the first board, another session starts again from them
+    start_mines: usize,
+    counted: bool,
+}
+impl FromResources for SessionState {
+    fn from_resources(resources: &Resources) -> Self {
+        let config = resources.get::<GameConfig>().unwrap();
+        let session = match config.mode {
+            GameMode::Session(mode) => Some(Session::new(mode, config.mine_count, config.shape.block_count())),
+            _ => None,
+        };
+        SessionState { session, start_mines: config.mine_count, counted: false }
+    }
+}
+
+/// Runs the clock of a session from board to board. A board over is counted
+/// and the next one starts at once, until the session ends.
+fn session_system(
+    time: Res<Time>,
+    game_log: Res<GameLog>,
+    mut session_state: ResMut<SessionState>,
+    mut text_query: Query<&mut Text, With<RefreshButton>>,
+    mut last_action_text: ResMut<LastActionText>,
+    mut game_results: ResMut<GameResults>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let session_state = &mut *session_state;
+    let session = match &mut session_state.session {
+        Some(session) => session,
+        None => return,
+    };
+    match game_state.current() {
+        GameState::Ready | GameState::Running if game_state.next().is_none() => {
+            if session.tick(time.delta_seconds()) {
+                println!("{} over: {}", session.mode.name(), session.summary());
+                game_results.0 = session.summary();
+                show_result(&mut text_query, &mut last_action_text, "Time's up!");
+                game_state.set_next(GameState::Over).unwrap();
+            }
+        }
+        GameState::Over if !session_state.counted && !session.over => {
+            session_state.counted = true;
+            if last_action_text.0 == "Finished!" {
+                session.board_won(game_log.elapsed);
+            } else {
+                session.board_lost();
+            }
+            if session.over {
+                println!("{} over: {}", session.mode.name(), session.summary());
+                game_results.0 = session.summary();
+            } else {
+                show_result(&mut text_query, &mut last_action_text, NEW_GAME_TEXT);
+                game_state.set_next(GameState::Prepare).unwrap();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Gives the next board the mines of the session, and a new game once the
+/// session is over starts another one.
+fn next_board(mut config: ResMut<GameConfig>, mut session_state: ResMut<SessionState>) {
+    let session_state = &mut *session_state;
+    let session = match &mut session_state.session {
+        Some(session) => session,
+        None => return,
+    };
+    if session.over {
+        *session = Session::new(session.mode, session_state.start_mines, config.shape.block_count());
+    }
+    session_state.counted = false;
+    config.mine_count = session.mine_count;
+}
+
+fn session_status(session_state: Res<SessionState>, mut mode_status: ResMut<ModeStatus>) {
+    if let Some(session) = &session_state.session {
+        let status = format!("{} left, board {}, mines: {}", session.clock(), session.won + session.lost + 1, session.mine_count);
+        mode_status.0.insert(session.mode.name(), status);
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use crate::mine_core::{ ClickResult, MinePlayground };
+use crate::versus::Versus;
+use super::{
+    get_block_index_by_cursor_pos, show_result, BlockTints, CursorLocation, GameConfig, GameMode, GameResults, GameState, LastActionText,
+    LayoutSource, MapData, ModeStatus, RefreshButton, ViewLayer, ViewScroll,
+};
+
+/// Two players at one mouse, see `versus_system`.
+pub(super) struct VersusPlugin;
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_resource(VersusGame::default())
+            .add_system(versus_system.system())
+            .add_system(versus_tint.system())
+            .add_system(versus_status.system());
+    }
+}
+
+/// The scores and the turn of the current versus game.
+#[derive(Default)]
+struct VersusGame {
+    versus: Option<Versus>,
+    /// the board played, a new one starts a new game
+    board: Option<Entity>,
+}
+
+/// In versus games the players share the mouse: a left click opens a block for
+/// the player to play, a right click puts or removes one of their flags.
+fn versus_system(
+    btns: Res<Input<MouseButton>>,
+    cursor_pos: Res<CursorLocation>,
+    config: Res<GameConfig>,
+    scroll: Res<ViewScroll>,
+    view_layer: Res<ViewLayer>,
+    layout_source: Res<LayoutSource>,
+    mut mquery: Query<&mut MinePlayground>,
+    map_data: Res<MapData>,
+    mut versus_game: ResMut<VersusGame>,
+    mut text_query: Query<&mut Text, With<RefreshButton>>,
+    mut last_action_text: ResMut<LastActionText>,
+    mut game_results: ResMut<GameResults>,
+    mut game_state: ResMut<State<GameState>>,
+) {
+    let rule = match &config.mode {
+        GameMode::Versus(rule) => *rule,
+        _ => return,
+    };
+    let versus_game = &mut *versus_game;
+    if versus_game.board != Some(map_data.map_entity) {
+        let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+        versus_game.board = Some(map_data.map_entity);
+        versus_game.versus = Some(Versus::new(&mut mp, rule));
+    }
+    let versus = versus_game.versus.as_mut().unwrap();
+    match game_state.current() {
+        GameState::Ready | GameState::Running => {},
+        _ => return,
+    }
+    let (reveal, flag) = (btns.just_released(MouseButton::Left), btns.just_released(MouseButton::Right));
+    let pos = match get_block_index_by_cursor_pos(cursor_pos.0, &config, &scroll, &view_layer) {
+        Some(pos) if reveal || flag => pos,
+        _ => return,
+    };
+    let mut mp: Mut<MinePlayground> = mquery.get_component_mut(map_data.map_entity).unwrap();
+    if reveal {
+        if let GameState::Ready = game_state.current() {
+            if mp.block(&pos).btype.mine_count() > 0 && layout_source.0.allows_first_click_fix() {
+                mp.fix(&pos);
+            }
+        }
+        if let ClickResult::Win = versus.reveal(&mut mp, &pos) {
+            game_state.set_next(GameState::Over).unwrap();
+            let result_text = match versus.leader() {
+                Some(0) => "Player 1 wins",
+                Some(_) => "Player 2 wins",
+                None => "Draw",
+            };
+            show_result(&mut text_query, &mut last_action_text, result_text);
+            game_results.0 = versus.results();
+            return;
+        }
+    } else {
+        versus.flag(&mut mp, &pos);
+    }
+    if let GameState::Ready = game_state.current() {
+        game_state.set_next(GameState::Running).unwrap();
+    }
+}
+
+/// Flags take the colour of their player, and the blocks a player opened a
+/// paler one.
+fn versus_tint(versus_game: Res<VersusGame>, mut tints: ResMut<BlockTints>) {
+    let versus = match &versus_game.versus {
+        Some(versus) => versus,
+        None => return,
+    };
+    for index in 0..versus.revealed_by.len() {
+        let color = match (versus.flagged_by[index], versus.revealed_by[index]) {
+            (Some(0), _) => Color::rgb(0.5, 0.7, 1.0),
+            (Some(_), _) => Color::rgb(1.0, 0.7, 0.4),
+            (_, Some(0)) => Color::rgb(0.85, 0.9, 1.0),
+            (_, Some(_)) => Color::rgb(1.0, 0.9, 0.8),
+            _ => continue,
+        };
+        tints.set(index, color);
+    }
+}
+
+fn versus_status(versus_game: Res<VersusGame>, mut mode_status: ResMut<ModeStatus>) {
+    if let Some(versus) = &versus_game.versus {
+        mode_status.0.insert("versus", format!("player {} to play, {} to {}", versus.turn + 1, versus.scores[0], versus.scores[1]));
+    }
+}
@@ -0,0 +1,130 @@
+use crate::board_file::{ Replay, ReplayAction, ReplayEvent };
+use crate::mine_core::{ BlockStatus, ClickResult, MinePlayground, Position };
+use crate::shape::BoardShape;
+
+/// Where the fastest win on a seeded board is kept, to race it again.
+pub fn best_path(seed: &str, width: usize, height: usize, mine_count: usize) -> String {
+    format!("best_seeded_{}_{}x{}_{}.rawvf", seed, width, height, mine_count)
+}
+
+/// Whether `replay` was played on the mines of `layout`, the mine under its
+/// first click moved away as the game does.
+pub fn played_on(replay: &Replay, layout: &[bool]) -> bool {
+    let mut mp = match MinePlayground::from_layout(BoardShape::rectangle(replay.width, replay.height), layout) {
+        Ok(mp) => mp,
+        Err(_) => return false,
+    };
+    if let Some(first) = replay.events.first() {
+        if first.action == ReplayAction::Reveal && mp.block(&first.pos).btype.mine_count() > 0 {
+            mp.fix(&first.pos);
+        }
+    }
+    replay.layout == layout || replay.layout == mp.mine_layout()
+}
+
+/// A recorded game played again on the same board, to race against: what it
+/// had opened at any time and where it clicked last.
+#[derive(Debug, Clone)]
+pub struct Ghost {
+    pub events: Vec<ReplayEvent>,
+    /// safe blocks shown after every event
+    shown: Vec<usize>,
+    /// for every block, the time the ghost opened it
+    opened_at: Vec<Option<f32>>,
+    /// safe blocks shown before the first event
+    start_shown: usize,
+}
+
+impl Ghost {
+    /// Plays `events` on the mines of `start`, from the blocks it shows, up to
+    /// the end of the recorded game.
+    pub fn new(start: &MinePlayground, events: &[ReplayEvent]) -> Result<Ghost, String> {
+        let mut mp = MinePlayground::from_stacked_layout(start.shape.clone(), &start.mine_counts(), start.max_stack)?;
+        let block_count = mp.shape.block_count();
+        for index in (0..block_count).filter(|&index| start.block_at(index).bstatus == BlockStatus::Shown) {
+            mp.show(&mp.shape.position(index))?;
+        }
+        let start_shown = mp.shown_count;
+        let mut shown = vec![];
+        let mut opened_at = vec![None; block_count];
+        for (done, event) in events.iter().enumerate() {
+            let result = match event.action {
+                ReplayAction::Reveal => mp.click(&event.pos),
+                ReplayAction::Flag => {
+                    mp.right_click(&event.pos);
+                    ClickResult::NothingHappened
+                }
+                ReplayAction::Chord => mp.chord(&event.pos),
+            };
+            // a lost game shows every block, none of them opened by the ghost
+            if let ClickResult::Wasted = result {
+                return Ok(Ghost { events: events[..done + 1].to_vec(), shown, opened_at, start_shown });
+            }
+            for (index, opened) in opened_at.iter_mut().enumerate() {
+                if opened.is_none() && mp.block_at(index).bstatus == BlockStatus::Shown && start.block_at(index).bstatus != BlockStatus::Shown {
+                    *opened = Some(event.time);
+                }
+            }
+            shown.push(mp.shown_count);
+        }
+        Ok(Ghost { events: events.to_vec(), shown, opened_at, start_shown })
+    }
+    /// Whether the ghost had opened block `index` by `time`.
+    pub fn opened(&self, index: usize, time: f32) -> bool {
+        self.opened_at[index].is_some_and(|at| at <= time)
+    }
+    /// The block of the last move by `time`.
+    pub fn cursor(&self, time: f32) -> Option<Position> {
+        self.events.iter().take_while(|event| event.time <= time).last().map(|event| event.pos)
+    }
+    /// How much later than the ghost the player got to `shown` safe blocks at
+    /// `time`, below 0 when ahead. `None` when the ghost never got that far.
+    pub fn delta(&self, shown: usize, time: f32) -> Option<f32> {
+        if shown <= self.start_shown {
+            return Some(0.0);
+        }
+        let reached = self.shown.iter().position(|&count| count >= shown)?;
+        Some(time - self.events[reached].time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_ghost() {
+        // two mines on the bottom row, a click at the top opens all but the
+        // block between them
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        layout[2] = true;
+        let start = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        let event = |time, x, y| ReplayEvent { time, action: ReplayAction::Reveal, pos: Position::new(x, y, 0) };
+        let ghost = Ghost::new(&start, &[event(1.0, 4, 4), event(3.0, 1, 0)]).unwrap();
+        assert!(!ghost.opened(24, 0.5));
+        assert!(ghost.opened(24, 1.0));
+        assert!(!ghost.opened(1, 2.0) && ghost.opened(1, 3.0));
+        assert_eq!(ghost.cursor(2.0), Some(Position::new(4, 4, 0)));
+        assert_eq!(ghost.cursor(0.5), None);
+        // the whole flood at 1.5s is half a second behind, the last block at 2s ahead
+        assert_eq!(ghost.delta(22, 1.5), Some(0.5));
+        assert_eq!(ghost.delta(23, 2.0), Some(-1.0));
+        assert_eq!(ghost.delta(24, 2.0), None);
+    }
+    #[test]
+    fn test_played_on() {
+        let mut layout = vec![false; 25];
+        layout[12] = true;
+        let event = |x, y| ReplayEvent { time: 0.0, action: ReplayAction::Reveal, pos: Position::new(x, y, 0) };
+        let replay = Replay { width: 5, height: 5, layout: layout.clone(), events: vec![event(0, 0)] };
+        assert!(played_on(&replay, &layout));
+        // the first click moved the mine in the middle to the first free block
+        let mut fixed = vec![false; 25];
+        fixed[0] = true;
+        let replay = Replay { layout: fixed, events: vec![event(2, 2)], ..replay };
+        assert!(played_on(&replay, &layout));
+        let replay = Replay { events: vec![event(0, 0)], ..replay };
+        assert!(!played_on(&replay, &layout));
+        assert!(!played_on(&replay, &[false; 36]));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::layout::SeededGenerator;
+use crate::mine_core::{ BlockStatus, BlockType, ClickResult, MinePlayground };
+use crate::shape::BoardShape;
+
+/// Planes of an observation, each holding one value per block.
+pub const CHANNELS: usize = 4;
+/// 1 for blocks not shown yet
+pub const HIDDEN_CHANNEL: usize = 0;
+/// flags on the block, over the most a block may hold
+pub const FLAG_CHANNEL: usize = 1;
+/// the tip of shown blocks, over the highest tip possible
+pub const NUMBER_CHANNEL: usize = 2;
+/// 1 for blocks cut out of the board
+pub const VOID_CHANNEL: usize = 3;
+
+/// What an agent may do, on blocks given by `BoardShape::index`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Reveal(usize),
+    /// one more flag, or none after the most a block may hold
+    Flag(usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RewardScheme {
+    /// for every block a reveal shows
+    pub reveal: f32,
+    pub win: f32,
+    pub loss: f32,
+    /// for actions that change nothing, like revealing a shown block
+    pub wasted: f32,
+    /// whether a mine under the first reveal moves away, as in the game
+    pub first_click_fix: bool,
+}
+
+impl Default for RewardScheme {
+    fn default() -> RewardScheme {
+        RewardScheme { reveal: 0.1, win: 1.0, loss: -1.0, wasted: -0.1, first_click_fix: true }
+    }
+}
+
+/// A headless game for training agents: `reset` deals a seeded board, `step`
+/// plays one action and returns the new observation, the reward and whether
+/// the game is over. Observations are `CHANNELS` planes of
+/// `BoardShape::block_count` values, channel after channel.
+pub struct MineEnv {
+    pub mine_count: usize,
+    pub rewards: RewardScheme,
+    pub playground: MinePlayground,
+    pub done: bool,
+    first_reveal: bool,
+}
+
+impl MineEnv {
+    pub fn new(shape: BoardShape, mine_count: usize, max_stack: usize, rewards: RewardScheme) -> Result<MineEnv, String> {
+        let playground = MinePlayground::generate_stacked(shape, &mine_count, max_stack, &SeededGenerator(0))?;
+        Ok(MineEnv { mine_count, rewards, playground, done: false, first_reveal: true })
+    }
+    pub fn reset(&mut self, seed: u64) -> Vec<f32> {
+        // the size was checked when the environment was made
+        self.playground = self.playground.regenerate(&self.mine_count, &SeededGenerator(seed)).unwrap();
+        self.done = false;
+        self.first_reveal = true;
+        self.observation()
+    }
+    pub fn step(&mut self, action: Action) -> (Vec<f32>, f32, bool) {
+        let reward = if self.done { 0.0 } else { self.play(action) };
+        (self.observation(), reward, self.done)
+    }
+    fn play(&mut self, action: Action) -> f32 {
+        let mp = &mut self.playground;
+        let (index, is_reveal) = match action {
+            Action::Reveal(index) => (index, true),
+            Action::Flag(index) => (index, false),
+        };
+        if index >= mp.shape.block_count() || !mp.shape.mask[index] {
+            return self.rewards.wasted;
+        }
+        let pos = mp.shape.position(index);
+        if !is_reveal {
+            if mp.block(&pos).bstatus == BlockStatus::Shown {
+                return self.rewards.wasted;
+            }
+            mp.right_click(&pos);
+            // agents have no use for question marks
+            if mp.block(&pos).bstatus == BlockStatus::QuestionMarked {
+                mp.right_click(&pos);
+            }
+            return 0.0;
+        }
+        if self.first_reveal && self.rewards.first_click_fix && mp.block(&pos).btype.mine_count() > 0 {
+            mp.fix(&pos);
+        }
+        self.first_reveal = false;
+        let shown_count = mp.shown_count;
+        match mp.click(&pos) {
+            ClickResult::Wasted => {
+                self.done = true;
+                self.rewards.loss
+            }
+            // only with lives set on the playground by hand
+            ClickResult::MineHit { .. } => self.rewards.loss,
+            ClickResult::Win => {
+                self.done = true;
+                self.rewards.reveal * (mp.shown_count - shown_count) as f32 + self.rewards.win
+            }
+            ClickResult::NothingHappened if mp.shown_count == shown_count => self.rewards.wasted,
+            ClickResult::NothingHappened => self.rewards.reveal * (mp.shown_count - shown_count) as f32,
+        }
+    }
+    pub fn observation(&self) -> Vec<f32> {
+        let mp = &self.playground;
+        let blocks = mp.shape.block_count();
+        let max_tip = (mp.shape.max_neighbours() * mp.max_stack) as f32;
+        let mut data = vec![0.0; CHANNELS * blocks];
+        for index in 0..blocks {
+            if !mp.shape.mask[index] {
+                data[VOID_CHANNEL * blocks + index] = 1.0;
+                continue;
+            }
+            let block = mp.block_at(index);
+            match (block.bstatus, block.btype) {
+                (BlockStatus::Shown, BlockType::Tip(val)) => data[NUMBER_CHANNEL * blocks + index] = val as f32 / max_tip,
+                (BlockStatus::Shown, _) => {}
+                (BlockStatus::Flaged(count), _) => {
+                    data[HIDDEN_CHANNEL * blocks + index] = 1.0;
+                    data[FLAG_CHANNEL * blocks + index] = count as f32 / mp.max_stack as f32;
+                }
+                _ => data[HIDDEN_CHANNEL * blocks + index] = 1.0,
+            }
+        }
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_env_episode() {
+        let mut env = MineEnv::new(BoardShape::rectangle(9, 9), 10, 1, RewardScheme::default()).unwrap();
+        let first = env.reset(3);
+        assert_eq!(first.len(), CHANNELS * 81);
+        assert!(first[..81].iter().all(|&hidden| hidden == 1.0));
+        assert_eq!(env.reset(3), first);
+        let (observation, reward, done) = env.step(Action::Flag(0));
+        assert_eq!((observation[81], reward, done), (1.0, 0.0, false));
+        let (observation, _, _) = env.step(Action::Flag(0));
+        assert_eq!(observation[81], 0.0);
+        // the first reveal never loses
+        let (_, reward, _) = env.step(Action::Reveal(40));
+        assert!(reward > 0.0);
+        assert_eq!(env.step(Action::Reveal(40)).1, env.rewards.wasted);
+        let mine = env.playground.mine_layout().iter().position(|&is_mine| is_mine).unwrap();
+        assert_eq!(env.step(Action::Reveal(mine)), (env.observation(), env.rewards.loss, true));
+        assert_eq!(env.step(Action::Reveal(mine)).1, 0.0);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::mine_core::{ BlockStatus, MinePlayground, Position };
+use crate::solver::{ Cell, Knowledge, Reason };
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HintKind {
+    Safe,
+    Mine,
+    /// nothing is certain, the block is the least likely mine
+    Guess,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub pos: Position,
+    pub kind: HintKind,
+    pub text: String,
+}
+
+/// A block the player can be sure about, with the reason in a few words, or
+/// the least risky guess when there is none. Mines already flagged aren't
+/// hinted again. `None` once the game is over and on boards with stacked
+/// mines, which the solver can't read.
+pub fn hint(mp: &MinePlayground) -> Option<Hint> {
+    if mp.max_stack > 1 {
+        return None;
+    }
+    let mut knowledge = Knowledge::from_playground(mp);
+    if knowledge.cells.iter().all(|&cell| cell != Cell::Hidden) {
+        return None;
+    }
+    loop {
+        let deductions = knowledge.deduce_with_reasons();
+        let useful = deductions.iter().find(|(deduction, _)| {
+            !deduction.is_mine || !matches!(mp.block_at(deduction.index).bstatus, BlockStatus::Flaged(_))
+        });
+        if let Some((deduction, reason)) = useful {
+            return Some(Hint {
+                pos: mp.shape.position(deduction.index),
+                kind: if deduction.is_mine { HintKind::Mine } else { HintKind::Safe },
+                text: explain(&knowledge, deduction.is_mine, reason),
+            });
+        }
+        // every deduced mine is flagged, they may lead further
+        if deductions.is_empty() {
+            break;
+        }
+        for (deduction, _) in deductions {
+            knowledge.cells[deduction.index] = Cell::Mine;
+        }
+    }
+    let odds = knowledge.mine_probabilities();
+    (0..odds.len())
+        .filter(|&index| knowledge.cells[index] == Cell::Hidden)
+        .min_by(|&a, &b| odds[a].partial_cmp(&odds[b]).unwrap())
+        .map(|index| Hint {
+            pos: mp.shape.position(index),
+            kind: HintKind::Guess,
+            text: format!("no safe move, least risky guess ({:.0}% mine)", odds[index] * 100.0),
+        })
+}
+
+fn explain(knowledge: &Knowledge, is_mine: bool, reason: &Reason) -> String {
+    let tip = |index: usize| match knowledge.cells[index] {
+        Cell::Shown(val) => val,
+        _ => 0,
+    };
+    match *reason {
+        Reason::Single(number) => {
+            let count = |wanted: Cell| knowledge.neighbours[number].iter().filter(|&&i| knowledge.cells[i] == wanted).count();
+            let (val, known, hidden) = (tip(number), count(Cell::Mine), count(Cell::Hidden));
+            match (is_mine, known) {
+                (true, 0) => format!("mine: the {} has exactly {}", val, plural(hidden, "hidden neighbour")),
+                (true, _) => format!("mine: the {} needs {} and has {}", val, plural(val - known, "more mine"), plural(hidden, "hidden neighbour")),
+                (false, _) => format!("safe: the {} already touches {}", val, plural(val, "mine")),
+            }
+        }
+        Reason::Subset(small, big) => {
+            let (small, big) = (tip(small), tip(big));
+            if is_mine {
+                format!("mine: {}-{} pattern, the {} needs more mines than the {} can hold", small, big, big, small)
+            } else {
+                format!("safe: {}-{} pattern, the {} gets all its mines next to the {}", small, big, big, small)
+            }
+        }
+        Reason::MineCount if is_mine => String::from("mine: the mines left fill every hidden block"),
+        Reason::MineCount => format!("safe: all {} mines are found", knowledge.mine_count),
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    format!("{} {}{}", count, word, if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::BoardShape;
+    #[test]
+    fn test_hint() {
+        // a 1 in the corner with a single hidden neighbour
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&Position::new(4, 4, 0));
+        let found = hint(&mp).unwrap();
+        assert_eq!(found.pos, Position::new(0, 0, 0));
+        assert_eq!(found.kind, HintKind::Mine);
+        assert_eq!(found.text, "mine: the 1 has exactly 1 hidden neighbour");
+        // with the mine flagged, the whole board is done but for it
+        mp.right_click(&Position::new(0, 0, 0));
+        assert_eq!(hint(&mp), None);
+        // nothing shown yet, every block is a guess
+        let mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        let found = hint(&mp).unwrap();
+        assert_eq!(found.kind, HintKind::Guess);
+        assert_eq!(found.text, "no safe move, least risky guess (4% mine)");
+    }
+    #[test]
+    fn test_least_risky_guess() {
+        // numbers around an opening: the worst of them gives (1, 3) 40%, more
+        // than the blocks away from them, but only 14% of the layouts have a
+        // mine there
+        let mut layout = vec![false; 49];
+        for index in [2, 6, 9, 16, 19, 27, 29, 34, 35, 42, 44, 47, 48] {
+            layout[index] = true;
+        }
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(7, 7), &layout).unwrap();
+        mp.click(&Position::new(4, 4, 0));
+        let found = hint(&mp).unwrap();
+        assert_eq!((found.pos, found.kind), (Position::new(1, 3, 0), HintKind::Guess));
+        assert_eq!(found.text, "no safe move, least risky guess (14% mine)");
+    }
+}
@@ -0,0 +1,249 @@
+use std::fs;
+use rand::{
+    distributions::{ Distribution, WeightedIndex },
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+use crate::board_file::{ read_mbf, read_rawvf };
+use crate::mine_core::{ MinePlayground, Position };
+use crate::shape::BoardShape;
+use crate::solver::is_solvable_from;
+
+const NO_GUESS_ATTEMPTS: usize = 1000;
+const CLUSTER_WEIGHT: f64 = 4.0;
+const EDGE_WEIGHT: f64 = 0.3;
+
+pub trait LayoutGenerator: Send + Sync {
+    /// A row-major mine layout of `width * height` blocks, leaving the blocks
+    /// cut out of the shape empty.
+    fn generate(&self, shape: &BoardShape, mine_count: usize) -> Result<Vec<bool>, String>;
+    /// A block the generator guarantees to be an opening.
+    fn safe_start(&self, _shape: &BoardShape) -> Option<Position> {
+        None
+    }
+    /// Whether a mine under the first click may be moved away, generators whose
+    /// exact layout matters opt out.
+    fn allows_first_click_fix(&self) -> bool {
+        true
+    }
+    /// Whether the mined blocks may get extra mines stacked on them, the solver
+    /// only knows about one mine per block.
+    fn allows_stacked_mines(&self) -> bool {
+        true
+    }
+}
+
+/// Builds a generator from its name, e.g. `uniform`, `seeded:42`, `no-guess`,
+/// `fixed:board.mbf`, `clustered` or `low-density-edge` (also `sparse-edge`).
+pub fn by_name(name: &str) -> Result<Box<dyn LayoutGenerator>, String> {
+    let (kind, arg) = match name.find(':') {
+        Some(idx) => (&name[..idx], Some(&name[idx + 1..])),
+        None => (name, None),
+    };
+    match (kind, arg) {
+        ("uniform", None) => Ok(Box::new(UniformGenerator)),
+        ("seeded", Some(seed)) => match seed.parse() {
+            Ok(seed) => Ok(Box::new(SeededGenerator(seed))),
+            Err(_) => Err(format!("bad seed `{}`", seed)),
+        },
+        ("no-guess", None) => Ok(Box::new(NoGuessGenerator)),
+        ("fixed", Some(path)) => Ok(Box::new(FixedGenerator::from_file(path)?)),
+        ("clustered", None) => Ok(Box::new(ClusteredGenerator)),
+        ("low-density-edge", None) | ("sparse-edge", None) => Ok(Box::new(LowDensityEdgeGenerator)),
+        _ => Err(format!("unknown layout generator `{}`", name)),
+    }
+}
+
+fn shuffled_layout<R: Rng>(shape: &BoardShape, mine_count: usize, rng: &mut R) -> Vec<bool> {
+    let playable: Vec<usize> = (0..shape.mask.len()).filter(|&i| shape.mask[i]).collect();
+    let mut layout = vec![false; shape.mask.len()];
+    for &i in playable.choose_multiple(rng, mine_count) {
+        layout[i] = true;
+    }
+    layout
+}
+
+/// Places mines one at a time, each free block weighted by `weight` given the
+/// mines placed so far.
+fn weighted_layout<R: Rng, F: Fn(&[bool], usize) -> f64>(
+    shape: &BoardShape,
+    mine_count: usize,
+    rng: &mut R,
+    weight: F,
+) -> Result<Vec<bool>, String> {
+    if mine_count > shape.playable_count() {
+        return Err(String::from("Too many mines for the board!"));
+    }
+    let mut layout = vec![false; shape.mask.len()];
+    for _ in 0..mine_count {
+        let weights: Vec<f64> = (0..layout.len())
+            .map(|i| if layout[i] || !shape.mask[i] { 0.0 } else { weight(&layout, i) })
+            .collect();
+        let dist = WeightedIndex::new(&weights).map_err(|e| e.to_string())?;
+        layout[dist.sample(rng)] = true;
+    }
+    Ok(layout)
+}
+
+pub struct UniformGenerator;
+impl LayoutGenerator for UniformGenerator {
+    fn generate(&self, shape: &BoardShape, mine_count: usize) -> Result<Vec<bool>, String> {
+        Ok(shuffled_layout(shape, mine_count, &mut rand::thread_rng()))
+    }
+}
+
+/// Uniform layout that is always the same for the same seed.
+pub struct SeededGenerator(pub u64);
+impl LayoutGenerator for SeededGenerator {
+    fn generate(&self, shape: &BoardShape, mine_count: usize) -> Result<Vec<bool>, String> {
+        Ok(shuffled_layout(shape, mine_count, &mut StdRng::seed_from_u64(self.0)))
+    }
+}
+
+/// Layouts that open at the centre block and can be cleared from there by the
+/// solver alone.
+pub struct NoGuessGenerator;
+impl LayoutGenerator for NoGuessGenerator {
+    fn generate(&self, shape: &BoardShape, mine_count: usize) -> Result<Vec<bool>, String> {
+        let start = self.safe_start(shape).ok_or("The board has no blocks")?;
+        let mut opening = shape.neighbours(&start);
+        opening.push(start);
+        let free: Vec<usize> = (0..shape.mask.len())
+            .filter(|&i| shape.mask[i] && !opening.contains(&shape.position(i)))
+            .collect();
+        if mine_count > free.len() {
+            return Err(String::from("Too many mines to leave an opening"));
+        }
+        let mut rng = rand::thread_rng();
+        for _ in 0..NO_GUESS_ATTEMPTS {
+            let mut layout = vec![false; shape.mask.len()];
+            for &i in free.choose_multiple(&mut rng, mine_count) {
+                layout[i] = true;
+            }
+            let mp = MinePlayground::from_layout(shape.clone(), &layout)?;
+            if is_solvable_from(&mp, shape.index(&start)) {
+                return Ok(layout);
+            }
+        }
+        Err(String::from("No guess-free layout found, try fewer mines"))
+    }
+    /// The playable block closest to the centre.
+    fn safe_start(&self, shape: &BoardShape) -> Option<Position> {
+        let centre = [shape.width / 2, shape.height / 2, shape.depth / 2];
+        (0..shape.mask.len())
+            .filter(|&i| shape.mask[i])
+            .map(|i| shape.position(i))
+            .min_by_key(|pos| {
+                [pos.x, pos.y, pos.z].iter().zip(centre.iter())
+                    .map(|(&a, &b)| (a as i64 - b as i64).pow(2))
+                    .sum::<i64>()
+            })
+    }
+    fn allows_first_click_fix(&self) -> bool {
+        false
+    }
+    fn allows_stacked_mines(&self) -> bool {
+        false
+    }
+}
+
+/// Always the same board, read from an `.mbf` board or a RAWVF replay.
+pub struct FixedGenerator {
+    pub width: usize,
+    pub height: usize,
+    pub layout: Vec<bool>,
+}
+impl FixedGenerator {
+    pub fn from_file(path: &str) -> Result<FixedGenerator, String> {
+        let (width, height, layout) = if path.ends_with(".mbf") {
+            let mp = read_mbf(&fs::read(path).map_err(|e| e.to_string())?)?;
+            (mp.width, mp.height, mp.mine_layout())
+        } else {
+            let replay = read_rawvf(&fs::read_to_string(path).map_err(|e| e.to_string())?)?;
+            (replay.width, replay.height, replay.layout)
+        };
+        Ok(FixedGenerator { width, height, layout })
+    }
+}
+impl LayoutGenerator for FixedGenerator {
+    fn generate(&self, shape: &BoardShape, _mine_count: usize) -> Result<Vec<bool>, String> {
+        if (shape.width, shape.height, shape.depth) != (self.width, self.height, 1) {
+            return Err(format!("Fixed board is {}x{}", self.width, self.height));
+        }
+        Ok(self.layout.clone())
+    }
+    fn allows_first_click_fix(&self) -> bool {
+        false
+    }
+    fn allows_stacked_mines(&self) -> bool {
+        false
+    }
+}
+
+/// Mines prefer to sit next to other mines.
+pub struct ClusteredGenerator;
+impl LayoutGenerator for ClusteredGenerator {
+    fn generate(&self, shape: &BoardShape, mine_count: usize) -> Result<Vec<bool>, String> {
+        weighted_layout(shape, mine_count, &mut rand::thread_rng(), |layout, i| {
+            let mined = shape.neighbours(&shape.position(i)).iter()
+                .filter(|cur| layout[shape.index(cur)])
+                .count();
+            1.0 + CLUSTER_WEIGHT * mined as f64
+        })
+    }
+}
+
+/// Fewer mines along the outlines of the board.
+pub struct LowDensityEdgeGenerator;
+impl LayoutGenerator for LowDensityEdgeGenerator {
+    fn generate(&self, shape: &BoardShape, mine_count: usize) -> Result<Vec<bool>, String> {
+        weighted_layout(shape, mine_count, &mut rand::thread_rng(), |_, i| {
+            if shape.is_edge(&shape.position(i)) { EDGE_WEIGHT } else { 1.0 }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Topology;
+    #[test]
+    fn test_generators() {
+        let shape = BoardShape::rectangle(9, 9);
+        for name in ["uniform", "seeded:7", "no-guess", "clustered", "low-density-edge", "sparse-edge"].iter() {
+            let layout = by_name(name).unwrap().generate(&shape, 10).unwrap();
+            assert_eq!(layout.len(), 81);
+            assert_eq!(layout.iter().filter(|&&is_mine| is_mine).count(), 10);
+        }
+        assert!(ClusteredGenerator.generate(&shape, 82).is_err());
+        assert!(LowDensityEdgeGenerator.generate(&shape, 82).is_err());
+        assert_eq!(SeededGenerator(7).generate(&shape, 10), SeededGenerator(7).generate(&shape, 10));
+        assert!(by_name("seeded:x").is_err());
+        assert!(by_name("nope").is_err());
+    }
+    #[test]
+    fn test_no_guess_opening() {
+        let shape = BoardShape::new(9, 9, Topology::Torus);
+        let layout = NoGuessGenerator.generate(&shape, 10).unwrap();
+        let mp = MinePlayground::from_layout(shape, &layout).unwrap();
+        assert!(is_solvable_from(&mp, 4 * 9 + 4));
+    }
+    #[test]
+    fn test_layered_no_guess() {
+        let shape = BoardShape::rectangle(6, 6).with_depth(3);
+        let start = NoGuessGenerator.safe_start(&shape).unwrap();
+        assert_eq!(start.z, 1);
+        let layout = NoGuessGenerator.generate(&shape, 12).unwrap();
+        let mp = MinePlayground::from_layout(shape.clone(), &layout).unwrap();
+        assert!(is_solvable_from(&mp, shape.index(&start)));
+    }
+    #[test]
+    fn test_masked_generators() {
+        let shape = BoardShape::from_name("donut", 12, 12, Topology::Rectangle).unwrap();
+        for name in ["uniform", "no-guess", "clustered", "low-density-edge"].iter() {
+            let layout = by_name(name).unwrap().generate(&shape, 10).unwrap();
+            assert!(layout.iter().zip(shape.mask.iter()).all(|(&is_mine, &playable)| playable || !is_mine));
+        }
+    }
+}
@@ -0,0 +1,18 @@
+pub mod analysis;
+pub mod board_file;
+pub mod bot;
+pub mod daily;
+pub mod drill;
+pub mod ghost;
+pub mod gym;
+pub mod hint;
+pub mod layout;
+pub mod mine_core;
+pub mod neighbourhood;
+pub mod net;
+pub mod puzzle;
+pub mod session;
+pub mod shape;
+pub mod solver;
+pub mod topology;
+pub mod versus;
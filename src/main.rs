@@ -1,17 +1,293 @@
 mod game;
-mod mine_core;
-use std::env;
+use minesweeper::{ analysis, board_file, bot, daily, drill, ghost, hint, layout, mine_core, neighbourhood, net, puzzle, session, shape, topology, versus };
+use std::{env, fs, mem};
+const PUZZLE_PACK: &str = "assets/puzzles.txt";
+const USAGE: &str = "usage: ./minesweeper [--level NUM] [--generator NAME] [--topology NAME] [--shape NAME] [--neighbourhood NAME] [--depth NUM] [--max-mines NUM] [--lives NUM] [--bot-speed NUM] [--bot-games NUM] [--daily LEVEL] [--code CODE] [--puzzle NUM] [--edit NAME] [--drill NAME] [--versus RULE] [--session NAME] [--connect ADDRESS] [--name NAME] [--spectate ADDRESS] [--publish PORT] [--ghost FILE.rawvf|best] [--board FILE.mbf] [--replay FILE.rawvf]";
 fn main() {
     println!("Hello, minesweeper!");
     let args: Vec<String> = env::args().collect();
     let config_map: Vec<(usize, usize, usize)> = vec![(8, 8, 10), (16, 16, 40), (30, 16, 99)];
-    let (width, height, mine_count) = match args.len() {
-        1 => config_map[0],
-        3 => config_map[args[2].parse().unwrap_or(0)],
-        _ => {
-            panic!("usage: ./minesweeper --level NUM")
-        }
+    let (width, height, mine_count) = config_map[0];
+    let mut config = game::GameConfig {
+        width,
+        height,
+        mine_count,
+        generator: String::from("uniform"),
+        shape: shape::BoardShape::rectangle(width, height),
+        max_stack: 1,
+        bot_speed: 4.0,
+        lives: 1,
+        mode: game::GameMode::Classic,
+        publish: None,
+        ghost: None,
+        best_replay: None,
     };
-    println!("{:?}-{:?}-{:?}", width, height, mine_count);
-    game::game_app(game::GameConfig { width, height, mine_count });
+    let mut board_topology = topology::Topology::Rectangle;
+    let mut shape_name = String::from("full");
+    let mut depth = 1;
+    let mut board_neighbourhood = neighbourhood::Neighbourhood::Classic;
+    let mut bot_games = None;
+    let mut code = None;
+    let mut puzzle_index = None;
+    let mut edit_name = None;
+    let mut drills = None;
+    let mut server = None;
+    let mut player_name = String::from("player");
+    let mut spectating = false;
+    let mut ghost_name = None;
+    // the options picking a mode, only one is played at a time
+    let mut modes = vec![];
+    if args.len() % 2 == 0 {
+        panic!("{}", USAGE);
+    }
+    for pair in args[1..].chunks(2) {
+        match pair[0].as_str() {
+            "--level" => {
+                let (width, height, mine_count) = config_map[pair[1].parse().unwrap_or(0)];
+                config.width = width;
+                config.height = height;
+                config.mine_count = mine_count;
+            }
+            "--generator" => {
+                config.generator = pair[1].clone();
+            }
+            "--topology" => {
+                board_topology = topology::Topology::from_name(&pair[1]).unwrap();
+            }
+            "--shape" => {
+                shape_name = pair[1].clone();
+            }
+            "--neighbourhood" => {
+                board_neighbourhood = neighbourhood::Neighbourhood::from_name(&pair[1]).unwrap();
+            }
+            "--depth" => {
+                depth = pair[1].parse().expect("depth must be a number");
+            }
+            "--max-mines" => {
+                config.max_stack = pair[1].parse().expect("max mines must be a number");
+                if !mine_core::STACK_RANGE.contains(&config.max_stack) {
+                    panic!("a block holds 1 to 3 mines");
+                }
+            }
+            "--lives" => {
+                config.lives = pair[1].parse().expect("lives must be a number");
+                if config.lives == 0 {
+                    panic!("at least 1 life is needed");
+                }
+            }
+            "--bot-speed" => {
+                config.bot_speed = pair[1].parse().expect("bot speed must be a number");
+            }
+            "--bot-games" => {
+                bot_games = Some(pair[1].parse::<usize>().expect("bot games must be a number"));
+            }
+            "--daily" => {
+                config.mode = game::GameMode::Daily(daily::DailyChallenge::today(pair[1].parse().unwrap_or(0)));
+                modes.push("--daily");
+            }
+            "--code" => {
+                code = Some(pair[1].clone());
+                modes.push("--code");
+            }
+            "--puzzle" => {
+                puzzle_index = Some(pair[1].parse::<usize>().expect("puzzle must be a number"));
+                modes.push("--puzzle");
+            }
+            "--edit" => {
+                edit_name = Some(pair[1].clone());
+                modes.push("--edit");
+            }
+            "--drill" => {
+                drills = Some(match pair[1].as_str() {
+                    "all" => drill::PATTERNS.to_vec(),
+                    name => vec![drill::Pattern::from_name(name).unwrap_or_else(|e| panic!("{}", e))],
+                });
+                modes.push("--drill");
+            }
+            "--versus" => {
+                config.mode = game::GameMode::Versus(match pair[1].as_str() {
+                    "turns" => versus::MineRule::PassTurn,
+                    points => versus::MineRule::CostPoints(points.parse().expect("versus takes `turns` or the points a mine costs")),
+                });
+                modes.push("--versus");
+            }
+            "--session" => {
+                config.mode = game::GameMode::Session(session::SessionMode::from_name(&pair[1]).unwrap_or_else(|e| panic!("{}", e)));
+                modes.push("--session");
+            }
+            "--connect" => {
+                server = Some(pair[1].clone());
+                modes.push("--connect");
+            }
+            "--spectate" => {
+                server = Some(pair[1].clone());
+                spectating = true;
+                modes.push("--spectate");
+            }
+            "--publish" => {
+                config.publish = Some(pair[1].parse().expect("port must be a number"));
+            }
+            "--name" => {
+                player_name = pair[1].clone();
+            }
+            "--ghost" => {
+                ghost_name = Some(pair[1].clone());
+            }
+            "--board" => {
+                config.generator = format!("fixed:{}", pair[1]);
+            }
+            "--replay" => {
+                let text = fs::read_to_string(&pair[1]).expect("cannot read replay file");
+                config.mode = game::GameMode::Replay(board_file::read_rawvf(&text).unwrap().events);
+                config.generator = format!("fixed:{}", pair[1]);
+                modes.push("--replay");
+            }
+            _ => {
+                panic!("{}", USAGE)
+            }
+        }
+    }
+    // --edit may start from a puzzle
+    let played: Vec<&str> = modes.iter().cloned().filter(|&option| option != "--puzzle" || edit_name.is_none()).collect();
+    if played.len() > 1 {
+        panic!("{} can't be combined, only one mode is played at a time", played.join(" and "));
+    }
+    if let Some(address) = server {
+        // no name: watch only
+        let name = if spectating { String::new() } else { player_name };
+        config.mode = game::GameMode::Network(address, name);
+    }
+    if let game::GameMode::Daily(challenge) = &config.mode {
+        let challenge = *challenge;
+        let standard = config.generator == "uniform" && shape_name == "full" && board_topology == topology::Topology::Rectangle
+            && board_neighbourhood == neighbourhood::Neighbourhood::Classic && depth == 1 && config.max_stack == 1 && config.lives == 1;
+        if !standard {
+            panic!("--daily is played on the standard board, without other board options");
+        }
+        let (width, height, mine_count) = *config_map.get(challenge.level).expect("daily levels are 0, 1 and 2");
+        config.width = width;
+        config.height = height;
+        config.mine_count = mine_count;
+        config.generator = format!("seeded:{}", challenge.seed());
+    }
+    let seed = config.generator.strip_prefix("seeded:").map(String::from);
+    if let Some(name) = ghost_name {
+        if played.iter().any(|&option| option != "--daily") {
+            panic!("--ghost races a plain or a daily game, without {}", played.join(" or "));
+        }
+        let path = match (name.as_str(), &seed) {
+            ("best", Some(seed)) => ghost::best_path(seed, config.width, config.height, config.mine_count),
+            ("best", None) => panic!("--ghost best races the best replay of a seeded board, e.g. --generator seeded:42"),
+            (path, _) => path.to_string(),
+        };
+        let text = fs::read_to_string(&path).unwrap_or_else(|e| panic!("cannot read {}: {}", path, e));
+        let replay = board_file::read_rawvf(&text).unwrap_or_else(|e| panic!("{}", e));
+        // a scored daily game is played on today's board only
+        if matches!(config.mode, game::GameMode::Daily(_)) && name != "best" {
+            let daily_board = layout::by_name(&config.generator).unwrap()
+                .generate(&shape::BoardShape::rectangle(config.width, config.height), config.mine_count)
+                .unwrap();
+            if !ghost::played_on(&replay, &daily_board) {
+                panic!("--ghost with --daily races a replay of today's board, e.g. --ghost best");
+            }
+        }
+        config.ghost = Some(replay.events);
+        // the board exactly as recorded, mines moved by the first click included
+        config.generator = format!("fixed:{}", path);
+    }
+    if let Some(path) = config.generator.strip_prefix("fixed:") {
+        let board = layout::FixedGenerator::from_file(path).unwrap();
+        config.width = board.width;
+        config.height = board.height;
+        config.mine_count = board.layout.iter().filter(|&&is_mine| is_mine).count();
+    }
+    let build_shape = |width, height| shape::BoardShape::from_name(&shape_name, width, height, board_topology).unwrap()
+        .with_depth(depth)
+        .with_neighbourhood(board_neighbourhood).unwrap();
+    if let Some(games) = bot_games {
+        // headless: the bot plays every level, nothing is drawn
+        let generator = layout::by_name(&config.generator).unwrap();
+        for (level, &(width, height, mine_count)) in config_map.iter().enumerate() {
+            match bot::play_games(build_shape(width, height), mine_count, &*generator, games) {
+                Ok(wins) => println!("level {} ({}x{}, {} mines): won {}/{} ({:.1}%)",
+                    level, width, height, mine_count, wins, games, wins as f32 * 100.0 / games.max(1) as f32),
+                Err(e) => println!("level {}: {}", level, e),
+            }
+        }
+        return;
+    }
+    if let Some(code) = code {
+        // the code brings its own board and rules
+        config.load_code(&code).unwrap_or_else(|e| panic!("{}", e));
+    } else if let Some(index) = puzzle_index {
+        let text = fs::read_to_string(PUZZLE_PACK).expect("cannot read the puzzle pack");
+        let mut puzzles = puzzle::read_pack(&text).unwrap_or_else(|e| panic!("{}", e));
+        if index >= puzzles.len() {
+            panic!("there are {} puzzles, numbered from 0", puzzles.len());
+        }
+        config.load_puzzle(puzzles.swap_remove(index));
+    } else if let Some(patterns) = drills {
+        config.load_drills(patterns);
+    } else {
+        config.shape = build_shape(config.width, config.height);
+        config.width = config.shape.width;
+        config.height = config.shape.height;
+        match layout::by_name(&config.generator) {
+            Err(e) => panic!("{}", e),
+            Ok(generator) if config.max_stack > 1 && !generator.allows_stacked_mines() => {
+                panic!("--max-mines can't be used with the {} generator", config.generator)
+            }
+            _ => {}
+        }
+        // tips above 26 have no sprites
+        if config.shape.max_neighbours() * config.max_stack > 26 {
+            panic!("--max-mines {} allows too many mines around a block", config.max_stack);
+        }
+    }
+    if matches!(config.mode, game::GameMode::Versus(_)) && config.lives > 1 {
+        panic!("--versus can't be combined with --lives");
+    }
+    // every board of a session is a fresh beginner board, survival makes it
+    // denser than other generators can lay out
+    if matches!(config.mode, game::GameMode::Session(_)) && (config.lives > 1 || config.publish.is_some()
+        || config.generator != "uniform" || (config.width, config.height, config.mine_count) != config_map[0]) {
+        panic!("--session plays uniform beginner boards, without --level, --generator, --lives, --publish or --board");
+    }
+    if let Some(name) = edit_name {
+        if config.max_stack > 1 {
+            panic!("--edit can't be combined with --max-mines");
+        }
+        // start from the puzzle or the board given, or from an empty board
+        let board = match mem::replace(&mut config.mode, game::GameMode::Classic) {
+            game::GameMode::Puzzle(puzzle) => puzzle::Puzzle { name, ..puzzle },
+            _ => {
+                let layout = if config.generator.starts_with("fixed:") {
+                    layout::by_name(&config.generator).unwrap().generate(&config.shape, config.mine_count).unwrap()
+                } else {
+                    vec![false; config.shape.block_count()]
+                };
+                let shown = vec![false; layout.len()];
+                puzzle::Puzzle { name, shape: config.shape.clone(), layout, shown }
+            }
+        };
+        config.generator = String::from("editor");
+        config.mode = game::GameMode::Editor(board);
+    }
+    // spectators are told the size of the board only, and RAWVF replays know
+    // nothing else either
+    let plain_board = config.shape.mask.iter().all(|&playable| playable) && config.shape.depth == 1
+        && config.shape.topology == topology::Topology::Rectangle && config.shape.neighbourhood == neighbourhood::Neighbourhood::Classic;
+    if config.ghost.is_some() && (!plain_board || config.max_stack > 1) {
+        panic!("--ghost races on a plain rectangle board, without --shape, --topology, --depth, --neighbourhood or --max-mines");
+    }
+    // only boards started from the seed have a best replay
+    let seeded = config.generator.starts_with("seeded:") || config.ghost.is_some();
+    if seeded && plain_board && config.max_stack == 1 && matches!(config.mode, game::GameMode::Classic | game::GameMode::Daily(_)) {
+        config.best_replay = seed.map(|seed| ghost::best_path(&seed, config.width, config.height, config.mine_count));
+    }
+    if config.publish.is_some() && (!plain_board || config.max_stack > 1 || matches!(config.mode, game::GameMode::Network(..))) {
+        panic!("--publish needs a plain rectangle board of your own, without --shape, --topology, --depth, --neighbourhood, --max-mines or --connect");
+    }
+    println!("{:?}-{:?}-{:?}", config.width, config.height, config.mine_count);
+    game::game_app(config);
 }
@@ -1,75 +1,172 @@
-use rand::seq::SliceRandom;
+use std::cmp;
+use std::sync::Arc;
+use rand::{ rngs::StdRng, seq::SliceRandom, SeedableRng };
+use crate::layout::{ LayoutGenerator, UniformGenerator };
+use crate::neighbourhood::Neighbourhood;
+use crate::shape::BoardShape;
+use crate::topology::Topology;
 static SIZE_RANGE: std::ops::Range<usize> = 5..200;
 static MINE_COUNT_RANGE: std::ops::Range<usize> = 1..100;
+pub static STACK_RANGE: std::ops::RangeInclusive<usize> = 1..=3;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BlockType {
-    Mine,
+    /// how many mines the block holds, always 1 unless mines stack
+    Mine(usize),
     Space,
+    /// total mines around, stacked ones counted each
     Tip(usize),
 }
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BlockStatus {
     Shown,
     Hidden,
     QuestionMarked,
-    Flaged,
+    /// how many mines the player thinks the block holds
+    Flaged(usize),
 }
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
+    /// layer, always 0 on flat boards
+    pub z: usize,
 }
-#[derive(Debug)]
+/// A copy of one block of the board, see `MinePlayground::block`.
+#[derive(Debug, Clone, Copy)]
 pub struct MineBlock {
     pub btype: BlockType,
     pub bstatus: BlockStatus,
     pub pos: Position,
 }
+/// A block in 16 bits: the status in the high byte, the tip or the mine count
+/// (with `MINE_BIT` set) in the low one.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct PackedBlock(u16);
+const MINE_BIT: u16 = 0x80;
+const FLAG_BIT: u16 = 0x10;
 pub struct MinePlayground {
     pub shown_count: usize,
     safety_block_count: usize,
-    width: usize,
-    height: usize,
-    pub map: Vec<Vec<MineBlock>>,
+    pub width: usize,
+    pub height: usize,
+    pub shape: BoardShape,
+    /// most mines a single block may hold, right clicks cycle up to it
+    pub max_stack: usize,
+    /// mines that can be hit before the game is lost, 1 unless set
+    pub lives: usize,
+    /// mines hit so far, the last one ends the game
+    pub exploded: Vec<Position>,
+    /// indexed like `BoardShape::index`
+    blocks: Vec<PackedBlock>,
+    neighbour_table: Arc<NeighbourTable>,
+}
+/// The neighbours of every block, by `BoardShape::index`. Boards regenerated
+/// from each other share it.
+#[derive(Debug)]
+struct NeighbourTable {
+    /// the neighbours of block `i` are `table[start[i]..start[i + 1]]`
+    start: Vec<u32>,
+    table: Vec<u32>,
 }
 #[derive(Debug)]
 pub enum ClickResult {
     Wasted,
+    /// a mine was hit but there were lives to spare
+    MineHit { lives_left: usize },
     NothingHappened,
     Win,
 }
-impl Default for MineBlock {
-    fn default() -> MineBlock {
-        MineBlock {
-            bstatus: BlockStatus::Hidden,
-            btype: BlockType::Space,
-            pos: Position { x: 0, y: 0},
-        }
-    }
-}
 impl BlockType {
-    fn increase (&mut self) {
+    fn increase (&mut self, weight: usize) {
         *self = match *self {
-            Self::Tip(val) => Self::Tip(val + 1),
-            Self::Space => Self::Tip(1),
-            Self::Mine => Self::Mine,
+            Self::Tip(val) => Self::Tip(val + weight),
+            Self::Space => Self::Tip(weight),
+            Self::Mine(count) => Self::Mine(count),
         }
     }
-    fn decrease (&mut self) {
+    fn decrease (&mut self, weight: usize) {
         *self = match *self {
-            Self::Tip(val @ 2..=8) => Self::Tip(val - 1),
-            Self::Mine => Self::Mine,
+            Self::Tip(val) if val > weight => Self::Tip(val - weight),
+            Self::Mine(count) => Self::Mine(count),
             _ => Self::Space,
         }
     }
+    pub fn mine_count(&self) -> usize {
+        match *self {
+            Self::Mine(count) => count,
+            _ => 0,
+        }
+    }
+}
+impl NeighbourTable {
+    fn new(shape: &BoardShape) -> NeighbourTable {
+        let mut start = vec![0];
+        let mut table = vec![];
+        for index in 0..shape.block_count() {
+            // blocks cut out of the board have no neighbours
+            if shape.mask[index] {
+                table.extend(shape.neighbours(&shape.position(index)).iter().map(|cur| shape.index(cur) as u32));
+            }
+            start.push(table.len() as u32);
+        }
+        NeighbourTable { start, table }
+    }
+    fn get(&self, index: usize) -> &[u32] {
+        &self.table[self.start[index] as usize..self.start[index + 1] as usize]
+    }
+}
+impl Position {
+    pub fn new(x: usize, y: usize, z: usize) -> Position {
+        Position { x, y, z }
+    }
 }
-impl MineBlock {
-    fn add_tip(&mut self) {
-        self.btype.increase();
+impl PackedBlock {
+    fn btype(self) -> BlockType {
+        let low = self.0 & 0xff;
+        match (low & MINE_BIT, low & !MINE_BIT) {
+            (0, 0) => BlockType::Space,
+            (0, val) => BlockType::Tip(val as usize),
+            (_, count) => BlockType::Mine(count as usize),
+        }
+    }
+    fn bstatus(self) -> BlockStatus {
+        match self.0 >> 8 {
+            0 => BlockStatus::Hidden,
+            1 => BlockStatus::Shown,
+            2 => BlockStatus::QuestionMarked,
+            flags => BlockStatus::Flaged((flags & !FLAG_BIT) as usize),
+        }
     }
-    fn remove_tip(&mut self) {
-        self.btype.decrease();
+    fn set_btype(&mut self, btype: BlockType) {
+        let low = match btype {
+            BlockType::Space => 0,
+            BlockType::Tip(val) => val as u16,
+            BlockType::Mine(count) => MINE_BIT | count as u16,
+        };
+        self.0 = (self.0 & 0xff00) | low;
+    }
+    fn set_bstatus(&mut self, bstatus: BlockStatus) {
+        let high = match bstatus {
+            BlockStatus::Hidden => 0,
+            BlockStatus::Shown => 1,
+            BlockStatus::QuestionMarked => 2,
+            BlockStatus::Flaged(count) => FLAG_BIT | count as u16,
+        };
+        self.0 = (self.0 & 0xff) | high << 8;
+    }
+    fn mine_count(self) -> usize {
+        if self.0 & MINE_BIT == 0 { 0 } else { (self.0 & 0x7f) as usize }
+    }
+    fn add_tip(&mut self, weight: usize) {
+        let mut btype = self.btype();
+        btype.increase(weight);
+        self.set_btype(btype);
+    }
+    fn remove_tip(&mut self, weight: usize) {
+        let mut btype = self.btype();
+        btype.decrease(weight);
+        self.set_btype(btype);
     }
 }
 
@@ -78,136 +175,291 @@ impl MinePlayground {
         if !SIZE_RANGE.contains(&width) || !SIZE_RANGE.contains(&height) || !MINE_COUNT_RANGE.contains(&mine_count) {
             return Err(String::from("Parameters not in specific range!"));
         }
-        let seeds_length = width * height;
-        let mut mine_seeds: Vec<bool> = Vec::with_capacity(seeds_length.into());
-        for i in 0..seeds_length {
-            if i < mine_count { mine_seeds.push(true); }
-            else { mine_seeds.push(false); }
-        }
-        let mut rng = rand::thread_rng();
-        mine_seeds.shuffle(&mut rng);
-        let mut mine_map: Vec<Vec<MineBlock>> = vec![];
-        for i in 0..height {
-            mine_map.push(mine_seeds[i * width..i * width + width].iter().enumerate().map(|(j, &is_mine_block)| {
-                MineBlock {
-                    btype: if is_mine_block { BlockType::Mine } else { BlockType::Space },
-                    pos: Position { x: j, y: i },
-                    ..Default::default()
-                }
-            }).collect());
-        }
-        for y in 0..mine_map.len() {
-            let row = &mine_map[y];
-            for x in 0..row.len() {
-                if let BlockType::Space = mine_map[y][x].btype {
-                    let surroundings = get_surroundings(&x, &y, &width, &height);
-                    for (cur_x, cur_y) in surroundings.iter() {
-                        if let BlockType::Mine = mine_map[*cur_y][*cur_x].btype {
-                            mine_map[y][x].add_tip();
-                        }
-                    }
+        MinePlayground::generate(BoardShape::rectangle(width, height), &mine_count, &UniformGenerator)
+    }
+    pub fn generate(shape: BoardShape, &mine_count: &usize, generator: &dyn LayoutGenerator) -> Result<MinePlayground, String> {
+        MinePlayground::generate_stacked(shape, &mine_count, 1, generator)
+    }
+    /// Like `generate`, but up to `max_stack` of the `mine_count` mines may share
+    /// a block.
+    pub fn generate_stacked(shape: BoardShape, &mine_count: &usize, max_stack: usize, generator: &dyn LayoutGenerator) -> Result<MinePlayground, String> {
+        if !SIZE_RANGE.contains(&shape.width) || !SIZE_RANGE.contains(&shape.height) || !STACK_RANGE.contains(&max_stack) {
+            return Err(String::from("Parameters not in specific range!"));
+        }
+        let counts = stacked_layout(&shape, mine_count, max_stack, generator)?;
+        MinePlayground::from_stacked_layout(shape, &counts, max_stack)
+    }
+    /// A new board with the same shape and rules, faster than `generate_stacked`
+    /// as the neighbours are not looked up again.
+    pub fn regenerate(&self, &mine_count: &usize, generator: &dyn LayoutGenerator) -> Result<MinePlayground, String> {
+        let counts = stacked_layout(&self.shape, mine_count, self.max_stack, generator)?;
+        MinePlayground::with_neighbour_table(self.shape.clone(), &counts, self.max_stack, Some(self.neighbour_table.clone()))
+    }
+    /// Builds a playground with exactly the given mines, `layout` being indexed
+    /// like `BoardShape::index`.
+    pub fn from_layout(shape: BoardShape, layout: &[bool]) -> Result<MinePlayground, String> {
+        let counts: Vec<usize> = layout.iter().map(|&is_mine| is_mine as usize).collect();
+        MinePlayground::from_stacked_layout(shape, &counts, 1)
+    }
+    /// Like `from_layout`, with the number of mines on every block.
+    pub fn from_stacked_layout(shape: BoardShape, counts: &[usize], max_stack: usize) -> Result<MinePlayground, String> {
+        MinePlayground::with_neighbour_table(shape, counts, max_stack, None)
+    }
+    fn with_neighbour_table(
+        shape: BoardShape,
+        counts: &[usize],
+        max_stack: usize,
+        neighbour_table: Option<Arc<NeighbourTable>>,
+    ) -> Result<MinePlayground, String> {
+        let (width, height) = (shape.width, shape.height);
+        if !SIZE_RANGE.contains(&width) || !SIZE_RANGE.contains(&height) || !STACK_RANGE.contains(&max_stack) {
+            return Err(String::from("Parameters not in specific range!"));
+        }
+        if counts.len() != shape.block_count() {
+            return Err(format!("Layout has {} blocks, expected {}", counts.len(), shape.block_count()));
+        }
+        if counts.iter().zip(shape.mask.iter()).any(|(&count, &playable)| count > 0 && !playable) {
+            return Err(String::from("Mines outside of the board shape!"));
+        }
+        if counts.iter().any(|&count| count > max_stack) {
+            return Err(format!("More than {} mines on a block!", max_stack));
+        }
+        let mine_count = counts.iter().filter(|&&count| count > 0).count();
+        let mut blocks = vec![PackedBlock::default(); shape.block_count()];
+        let neighbour_table = neighbour_table.unwrap_or_else(|| Arc::new(NeighbourTable::new(&shape)));
+        for (index, &weight) in counts.iter().enumerate() {
+            if weight > 0 {
+                blocks[index].set_btype(BlockType::Mine(weight));
+                for &cur in neighbour_table.get(index) {
+                    blocks[cur as usize].add_tip(weight);
                 }
             }
         }
-        // println!("{:?}", mine_map);
-
         Ok(MinePlayground {
             shown_count: 0,
-            safety_block_count: height * width - mine_count,
+            safety_block_count: shape.playable_count() - mine_count,
             width,
             height,
-            map: mine_map,
+            shape,
+            max_stack,
+            lives: 1,
+            exploded: vec![],
+            blocks,
+            neighbour_table,
         })
     }
-    pub fn click(&mut self, x: &usize, y: &usize) -> ClickResult {
-        let mut block = &mut self.map[*y][*x];
-        if let BlockStatus::Hidden = block.bstatus {
-            match block.btype {
-                BlockType::Mine => {
-                    // game over
-                    for y in 0..self.height {
-                        for x in 0..self.width {
-                            self.map[y][x].bstatus = BlockStatus::Shown;
-                        }
-                    }
-                    return ClickResult::Wasted;
-                },
-                BlockType::Tip(_) => {
-                    block.bstatus = BlockStatus::Shown;
-                    self.shown_count += 1;
-                },
-                BlockType::Space => {
-                    block.bstatus = BlockStatus::Shown;
-                    let surroundings = get_surroundings(x, y, &self.width, &self.height);
+    pub fn block(&self, pos: &Position) -> MineBlock {
+        self.block_at(self.shape.index(pos))
+    }
+    /// The block at `BoardShape::index`.
+    pub fn block_at(&self, index: usize) -> MineBlock {
+        let block = self.blocks[index];
+        MineBlock { btype: block.btype(), bstatus: block.bstatus(), pos: self.shape.position(index) }
+    }
+    pub fn click(&mut self, pos: &Position) -> ClickResult {
+        if !self.shape.is_playable(pos) {
+            return ClickResult::NothingHappened;
+        }
+        let index = self.shape.index(pos);
+        if self.blocks[index].bstatus() != BlockStatus::Hidden {
+            return ClickResult::NothingHappened;
+        }
+        if self.blocks[index].mine_count() > 0 {
+            self.exploded.push(*pos);
+            if self.lives > 1 {
+                self.lives -= 1;
+                self.blocks[index].set_bstatus(BlockStatus::Shown);
+                return ClickResult::MineHit { lives_left: self.lives };
+            }
+            // game over
+            self.lives = 0;
+            for block in self.blocks.iter_mut() {
+                block.set_bstatus(BlockStatus::Shown);
+            }
+            return ClickResult::Wasted;
+        }
+        // open the block, and around every space opened on the way
+        let mut pending = vec![index];
+        self.blocks[index].set_bstatus(BlockStatus::Shown);
+        self.shown_count += 1;
+        while let Some(cur) = pending.pop() {
+            if self.blocks[cur].btype() != BlockType::Space {
+                continue;
+            }
+            for &next in self.neighbour_table.get(cur) {
+                let next = next as usize;
+                if self.blocks[next].bstatus() == BlockStatus::Hidden {
+                    self.blocks[next].set_bstatus(BlockStatus::Shown);
                     self.shown_count += 1;
-                    for (cur_x, cur_y) in surroundings.iter() {
-                        self.click(cur_x, cur_y);
-                    }
+                    pending.push(next);
                 }
             }
-            if self.shown_count == self.safety_block_count {
-                return ClickResult::Win;
-            }
+        }
+        if self.shown_count == self.safety_block_count {
+            return ClickResult::Win;
         }
         ClickResult::NothingHappened
     }
-    pub fn right_click(&mut self, x: &usize, y: &usize) {
-        let mut block = &mut self.map[*y][*x];
-        if let BlockStatus::Shown = block.bstatus {
-            return;
+    /// Shows one safe block without opening around it, for boards that start
+    /// partly revealed.
+    pub fn show(&mut self, pos: &Position) -> Result<(), String> {
+        if !self.shape.is_playable(pos) || self.block(pos).btype.mine_count() > 0 {
+            return Err(format!("({}, {}, {}) is not a safe block", pos.x, pos.y, pos.z));
         }
-        match block.bstatus {
-            BlockStatus::Hidden => { block.bstatus = BlockStatus::Flaged; }
-            BlockStatus::Flaged => { block.bstatus = BlockStatus::QuestionMarked; }
-            BlockStatus::QuestionMarked => { block.bstatus = BlockStatus::Hidden; }
-            _ => {}
+        let index = self.shape.index(pos);
+        if self.blocks[index].bstatus() != BlockStatus::Shown {
+            self.blocks[index].set_bstatus(BlockStatus::Shown);
+            self.shown_count += 1;
         }
+        Ok(())
     }
-    pub fn fix(&mut self, x: &usize, y: &usize) {
-        println!("fixing!-{:?}-{:?}", x, y);
-        self.map[*y][*x].btype = BlockType::Space;
-        let surroundings = get_surroundings(x, y, &self.width, &self.height);
-        for (cur_x, cur_y) in surroundings.iter() {
-            if let BlockType::Mine = self.map[*cur_y][*cur_x].btype {
-                self.map[*y][*x].add_tip();
-            }
-            self.map[*cur_y][*cur_x].remove_tip();
+    /// Reveals the hidden neighbours of a shown number once the flags and the
+    /// exploded mines around it add up to it.
+    pub fn chord(&mut self, pos: &Position) -> ClickResult {
+        if !self.shape.is_playable(pos) {
+            return ClickResult::NothingHappened;
         }
-        for new_y in 0..self.height {
-            for new_x in 0..self.width {
-                if new_x == *x && new_y == *y {
-                    return;
-                }
-                if self.map[new_y][new_x].btype != BlockType::Mine {
-                    self.map[new_y][new_x].btype = BlockType::Mine;
-                    let surroundings = get_surroundings(&new_x, &new_y, &self.width, &self.height);
-                    for (cur_x, cur_y) in surroundings.iter() {
-                        self.map[*cur_x][*cur_y].add_tip();
-                    }
-                    return;
+        let tip = match self.block(pos) {
+            MineBlock { bstatus: BlockStatus::Shown, btype: BlockType::Tip(val), .. } => val,
+            _ => return ClickResult::NothingHappened,
+        };
+        let surroundings = self.neighbours(pos);
+        let flags: usize = surroundings.iter().map(|cur| match self.block(cur) {
+            MineBlock { bstatus: BlockStatus::Flaged(count), .. } => count,
+            MineBlock { bstatus: BlockStatus::Shown, btype, .. } => btype.mine_count(),
+            _ => 0,
+        }).sum();
+        if flags != tip {
+            return ClickResult::NothingHappened;
+        }
+        let mut result = ClickResult::NothingHappened;
+        for cur in surroundings.iter() {
+            match self.click(cur) {
+                ClickResult::Wasted => return ClickResult::Wasted,
+                ClickResult::Win => result = ClickResult::Win,
+                ClickResult::MineHit { lives_left } if !matches!(result, ClickResult::Win) => {
+                    result = ClickResult::MineHit { lives_left };
                 }
+                _ => {}
             }
         }
+        result
+    }
+    pub fn neighbours(&self, pos: &Position) -> Vec<Position> {
+        self.neighbour_indices(self.shape.index(pos)).map(|cur| self.shape.position(cur)).collect()
+    }
+    /// Like `neighbours`, by `BoardShape::index` and without allocating.
+    pub fn neighbour_indices(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.neighbour_table.get(index).iter().map(|&cur| cur as usize)
+    }
+    pub fn mine_layout(&self) -> Vec<bool> {
+        self.mine_counts().iter().map(|&count| count > 0).collect()
+    }
+    /// Mines on every block, indexed like `BoardShape::index`.
+    pub fn mine_counts(&self) -> Vec<usize> {
+        self.blocks.iter().map(|block| block.mine_count()).collect()
+    }
+    pub fn right_click(&mut self, pos: &Position) {
+        if !self.shape.is_playable(pos) {
+            return;
+        }
+        let max_stack = self.max_stack;
+        let index = self.shape.index(pos);
+        let block = &mut self.blocks[index];
+        match block.bstatus() {
+            BlockStatus::Hidden => block.set_bstatus(BlockStatus::Flaged(1)),
+            BlockStatus::Flaged(count) if count < max_stack => block.set_bstatus(BlockStatus::Flaged(count + 1)),
+            BlockStatus::Flaged(_) => block.set_bstatus(BlockStatus::QuestionMarked),
+            BlockStatus::QuestionMarked => block.set_bstatus(BlockStatus::Hidden),
+            BlockStatus::Shown => {}
+        }
+    }
+    /// Puts `count` flags on a hidden block, 0 takes them away.
+    pub fn set_flags(&mut self, pos: &Position, count: usize) {
+        if !self.shape.is_playable(pos) {
+            return;
+        }
+        let block = &mut self.blocks[self.shape.index(pos)];
+        match block.bstatus() {
+            BlockStatus::Shown => {}
+            _ if count == 0 => block.set_bstatus(BlockStatus::Hidden),
+            _ => block.set_bstatus(BlockStatus::Flaged(count.min(self.max_stack))),
+        }
+    }
+    /// Overwrites a block, for boards mirrored from a server: their numbers
+    /// don't come from mines of their own.
+    pub fn set_block(&mut self, pos: &Position, btype: BlockType, bstatus: BlockStatus) {
+        let block = &mut self.blocks[self.shape.index(pos)];
+        let was_safe_shown = block.bstatus() == BlockStatus::Shown && block.mine_count() == 0;
+        let is_safe_shown = bstatus == BlockStatus::Shown && btype.mine_count() == 0;
+        self.shown_count = self.shown_count + is_safe_shown as usize - was_safe_shown as usize;
+        block.set_btype(btype);
+        block.set_bstatus(bstatus);
+    }
+    /// Moves the mines of `pos` to the first free playable block, for a first
+    /// click that must not hit a mine. A board without a free block is left
+    /// as it is.
+    pub fn fix(&mut self, pos: &Position) {
+        let index = self.shape.index(pos);
+        let weight = self.blocks[index].mine_count();
+        let new_index = match (0..self.blocks.len())
+            .find(|&new_index| new_index != index && self.blocks[new_index].mine_count() == 0 && self.shape.mask[new_index]) {
+            Some(new_index) if weight > 0 => new_index,
+            _ => return,
+        };
+        self.blocks[index].set_btype(BlockType::Space);
+        for &cur in self.neighbour_table.get(index) {
+            let cur = cur as usize;
+            let cur_weight = self.blocks[cur].mine_count();
+            if cur_weight > 0 {
+                self.blocks[index].add_tip(cur_weight);
+            }
+            self.blocks[cur].remove_tip(weight);
+        }
+        self.blocks[new_index].set_btype(BlockType::Mine(weight));
+        for &cur in self.neighbour_table.get(new_index) {
+            self.blocks[cur as usize].add_tip(weight);
+        }
+    }
+}
+
+/// Mines per block for `generate_stacked`: the generator picks the mined
+/// blocks, the extra mines are then stacked on them, the same way every time
+/// for the same layout.
+fn stacked_layout(shape: &BoardShape, mine_count: usize, max_stack: usize, generator: &dyn LayoutGenerator) -> Result<Vec<usize>, String> {
+    if max_stack > 1 && !generator.allows_stacked_mines() {
+        return Err(String::from("This layout generator can't stack mines"));
+    }
+    // on average every mined block holds half of the most it may hold
+    let mined_count = cmp::max((mine_count * 2 + max_stack) / (max_stack + 1), mine_count.div_ceil(max_stack));
+    if mined_count >= shape.playable_count() {
+        return Err(String::from("Too many mines for the board!"));
     }
+    let layout = generator.generate(shape, mined_count)?;
+    let mut counts: Vec<usize> = layout.iter().map(|&is_mine| is_mine as usize).collect();
+    let mut rng = StdRng::seed_from_u64(layout_seed(&layout));
+    for _ in mined_count..mine_count {
+        let stackable: Vec<usize> = (0..counts.len()).filter(|&i| counts[i] > 0 && counts[i] < max_stack).collect();
+        counts[*stackable.choose(&mut rng).unwrap()] += 1;
+    }
+    Ok(counts)
+}
+
+/// A seed that only depends on `layout`, unlike `DefaultHasher` the same with
+/// every Rust release: 64 blocks at a time mixed in with splitmix64.
+fn layout_seed(layout: &[bool]) -> u64 {
+    layout.chunks(64).fold(layout.len() as u64, |seed, chunk| {
+        let bits = chunk.iter().enumerate().fold(0u64, |bits, (i, &is_mine)| bits | (is_mine as u64) << i);
+        let mut z = (seed ^ bits).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
 }
 
-fn get_surroundings(&x: &usize, &y: &usize, &max_width: &usize, &max_height: &usize) -> Vec<(usize, usize)> {
-    let max_x = max_width - 1;
-    let max_y = max_height - 1;
-    let mut r = vec![];
-    if x > 0 { r.push((x - 1, y)); }
-    if x < max_x { r.push((x + 1, y)); }
-    if y > 0 {
-        r.push((x, y - 1));
-        if x > 0 { r.push((x - 1, y - 1)); }
-        if x < max_x { r.push((x + 1, y - 1)); }
-    }
-    if y < max_y {
-        r.push((x, y + 1));
-        if x > 0 { r.push((x - 1, y + 1)); }
-        if x < max_x { r.push((x + 1, y + 1)); }
-    }
-    r
+/// The 8 blocks around `(x, y)` on a plain rectangle.
+pub fn get_surroundings(&x: &usize, &y: &usize, &max_width: &usize, &max_height: &usize) -> Vec<(usize, usize)> {
+    Topology::Rectangle.neighbours(x, y, max_width, max_height, Neighbourhood::Classic)
 }
 
 #[cfg(test)]
@@ -219,6 +471,145 @@ mod tests{
         assert!(MinePlayground::init(&8, &8, &10).is_ok());
     }
     #[test]
+    fn test_from_layout() {
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        layout[24] = true;
+        let mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        assert_eq!(mp.block(&Position::new(0, 0, 0)).btype, BlockType::Mine(1));
+        assert_eq!(mp.block(&Position::new(1, 1, 0)).btype, BlockType::Tip(1));
+        assert_eq!(mp.block(&Position::new(2, 2, 0)).btype, BlockType::Space);
+        assert_eq!(mp.mine_layout(), layout);
+        assert!(MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout[1..]).is_err());
+        let mp = MinePlayground::from_layout(BoardShape::new(5, 5, Topology::Torus), &layout).unwrap();
+        assert_eq!(mp.block(&Position::new(4, 0, 0)).btype, BlockType::Tip(2));
+    }
+    #[test]
+    fn test_masked_playground() {
+        let mut shape = BoardShape::rectangle(5, 5);
+        shape.mask[1] = false;
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        let mut mp = MinePlayground::from_layout(shape.clone(), &layout).unwrap();
+        assert_eq!(mp.block(&Position::new(2, 0, 0)).btype, BlockType::Space);
+        assert!(matches!(mp.click(&Position::new(1, 0, 0)), ClickResult::NothingHappened));
+        assert!(matches!(mp.click(&Position::new(4, 4, 0)), ClickResult::Win));
+        assert_eq!(mp.shown_count, 23);
+        layout[1] = true;
+        assert!(MinePlayground::from_layout(shape, &layout).is_err());
+    }
+    #[test]
+    fn test_layered_playground() {
+        let shape = BoardShape::rectangle(5, 5).with_depth(3);
+        let mut layout = vec![false; shape.block_count()];
+        layout[shape.index(&Position::new(2, 2, 1))] = true;
+        let mut mp = MinePlayground::from_layout(shape, &layout).unwrap();
+        assert_eq!(mp.block(&Position::new(1, 1, 0)).btype, BlockType::Tip(1));
+        assert_eq!(mp.block(&Position::new(3, 3, 2)).btype, BlockType::Tip(1));
+        assert_eq!(mp.block(&Position::new(0, 0, 2)).btype, BlockType::Space);
+        mp.click(&Position::new(0, 0, 0));
+        // the blocks right above and below the mine only touch tips
+        assert_eq!(mp.shown_count, 72);
+        mp.click(&Position::new(2, 2, 0));
+        assert!(matches!(mp.click(&Position::new(2, 2, 2)), ClickResult::Win));
+        assert_eq!(BoardShape::rectangle(5, 5).with_depth(3).neighbours(&Position::new(2, 2, 1)).len(), 26);
+    }
+    #[test]
+    fn test_stacked_mines() {
+        let mut counts = vec![0; 25];
+        counts[2] = 3;
+        counts[24] = 1;
+        let mut mp = MinePlayground::from_stacked_layout(BoardShape::rectangle(5, 5), &counts, 3).unwrap();
+        assert_eq!(mp.block(&Position::new(3, 1, 0)).btype, BlockType::Tip(3));
+        assert_eq!(mp.block(&Position::new(3, 3, 0)).btype, BlockType::Tip(1));
+        let pos = Position::new(2, 0, 0);
+        for count in 1..=3 {
+            mp.right_click(&pos);
+            assert_eq!(mp.block(&pos).bstatus, BlockStatus::Flaged(count));
+        }
+        mp.right_click(&pos);
+        assert_eq!(mp.block(&pos).bstatus, BlockStatus::QuestionMarked);
+        // the whole stack moves away
+        mp.fix(&pos);
+        assert_eq!(mp.block(&Position::new(0, 0, 0)).btype, BlockType::Mine(3));
+        assert_eq!(mp.block(&Position::new(1, 1, 0)).btype, BlockType::Tip(3));
+        assert_eq!(mp.block(&Position::new(3, 1, 0)).btype, BlockType::Space);
+        assert_eq!(mp.block(&pos).btype, BlockType::Space);
+        assert!(MinePlayground::from_stacked_layout(BoardShape::rectangle(5, 5), &counts, 2).is_err());
+        // a mine on the first block moves past the click, the board can be won
+        let mut counts = vec![0; 81];
+        counts[0] = 1;
+        let mut mp = MinePlayground::from_stacked_layout(BoardShape::rectangle(9, 9), &counts, 1).unwrap();
+        mp.fix(&Position::new(0, 0, 0));
+        assert_eq!(mp.block(&Position::new(1, 0, 0)).btype, BlockType::Mine(1));
+        assert!(matches!(mp.click(&Position::new(0, 0, 0)), ClickResult::NothingHappened));
+        assert!(matches!(mp.click(&Position::new(8, 8, 0)), ClickResult::Win));
+        let mp = MinePlayground::generate_stacked(BoardShape::rectangle(9, 9), &30, 3, &UniformGenerator).unwrap();
+        assert_eq!(mp.mine_counts().iter().sum::<usize>(), 30);
+        let mp = mp.regenerate(&20, &UniformGenerator).unwrap();
+        assert_eq!((mp.max_stack, mp.mine_counts().iter().sum::<usize>()), (3, 20));
+        // seeded stacked boards stay the same with any Rust release
+        let mut layout = vec![false; 81];
+        layout[0] = true;
+        layout[80] = true;
+        assert_eq!(layout_seed(&layout), 15626075678510384279);
+    }
+    #[test]
+    fn test_chord() {
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        layout[2] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        let tip = Position::new(1, 1, 0);
+        mp.click(&tip);
+        assert!(matches!(mp.chord(&tip), ClickResult::NothingHappened));
+        assert_eq!(mp.shown_count, 1);
+        mp.right_click(&Position::new(0, 0, 0));
+        mp.right_click(&Position::new(2, 0, 0));
+        assert!(matches!(mp.chord(&tip), ClickResult::Win));
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&tip);
+        mp.right_click(&Position::new(1, 0, 0));
+        mp.right_click(&Position::new(0, 1, 0));
+        assert!(matches!(mp.chord(&tip), ClickResult::Wasted));
+    }
+    #[test]
+    fn test_lives() {
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        layout[2] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.lives = 2;
+        assert!(matches!(mp.click(&Position::new(0, 0, 0)), ClickResult::MineHit { lives_left: 1 }));
+        assert_eq!(mp.block(&Position::new(0, 0, 0)).bstatus, BlockStatus::Shown);
+        assert_eq!(mp.shown_count, 0);
+        // the exploded mine counts like a flag
+        let tip = Position::new(1, 1, 0);
+        mp.click(&tip);
+        mp.right_click(&Position::new(2, 0, 0));
+        assert!(matches!(mp.chord(&tip), ClickResult::Win));
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.lives = 2;
+        mp.click(&Position::new(0, 0, 0));
+        assert!(matches!(mp.click(&Position::new(2, 0, 0)), ClickResult::Wasted));
+        assert_eq!(mp.exploded, vec![Position::new(0, 0, 0), Position::new(2, 0, 0)]);
+        assert_eq!(mp.lives, 0);
+    }
+    #[test]
+    fn test_packed_block() {
+        let mut block = PackedBlock::default();
+        assert_eq!((block.btype(), block.bstatus()), (BlockType::Space, BlockStatus::Hidden));
+        for &btype in [BlockType::Tip(26), BlockType::Mine(3), BlockType::Space].iter() {
+            for &bstatus in [BlockStatus::Flaged(2), BlockStatus::QuestionMarked, BlockStatus::Shown].iter() {
+                block.set_btype(btype);
+                block.set_bstatus(bstatus);
+                assert_eq!((block.btype(), block.bstatus()), (btype, bstatus));
+            }
+        }
+        block.set_btype(BlockType::Mine(2));
+        assert_eq!(block.mine_count(), 2);
+    }
+    #[test]
     fn test_get_surroundings() {
         assert_eq!(get_surroundings(&9, &9, &10, &10), vec![(8, 9), (9, 8), (8, 8)]);
     }
@@ -0,0 +1,66 @@
+/// Which blocks around a block count for its tip and flood fill, on square
+/// grids. Hex boards always use their 6 touching blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Neighbourhood {
+    /// the 8 touching blocks
+    #[default]
+    Classic,
+    /// the 8 blocks a chess knight reaches
+    Knight,
+    /// the 4 blocks sharing an edge
+    Cross,
+    /// the 24 blocks of the 5x5 square around
+    Radius2,
+}
+
+const CLASSIC_OFFSETS: [(i64, i64); 8] = [(-1, 0), (1, 0), (0, -1), (-1, -1), (1, -1), (0, 1), (-1, 1), (1, 1)];
+const KNIGHT_OFFSETS: [(i64, i64); 8] = [(-2, -1), (-1, -2), (1, -2), (2, -1), (-2, 1), (-1, 2), (1, 2), (2, 1)];
+const CROSS_OFFSETS: [(i64, i64); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+impl Neighbourhood {
+    pub fn from_name(name: &str) -> Result<Neighbourhood, String> {
+        match name {
+            "classic" => Ok(Neighbourhood::Classic),
+            "knight" => Ok(Neighbourhood::Knight),
+            "cross" => Ok(Neighbourhood::Cross),
+            "radius-2" => Ok(Neighbourhood::Radius2),
+            _ => Err(format!("unknown neighbourhood `{}`", name)),
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Neighbourhood::Classic => "classic",
+            Neighbourhood::Knight => "knight",
+            Neighbourhood::Cross => "cross",
+            Neighbourhood::Radius2 => "radius-2",
+        }
+    }
+    /// `(dx, dy)` of every neighbour.
+    pub fn offsets(&self) -> Vec<(i64, i64)> {
+        match self {
+            Neighbourhood::Classic => CLASSIC_OFFSETS.to_vec(),
+            Neighbourhood::Knight => KNIGHT_OFFSETS.to_vec(),
+            Neighbourhood::Cross => CROSS_OFFSETS.to_vec(),
+            Neighbourhood::Radius2 => (-2..=2)
+                .flat_map(|dy| (-2..=2).map(move |dx| (dx, dy)))
+                .filter(|&offset| offset != (0, 0))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Topology;
+    #[test]
+    fn test_neighbourhoods() {
+        assert_eq!(Neighbourhood::Radius2.offsets().len(), 24);
+        let knight = Topology::Rectangle.neighbours(0, 0, 5, 5, Neighbourhood::Knight);
+        assert_eq!(knight, vec![(1, 2), (2, 1)]);
+        let cross = Topology::Torus.neighbours(0, 0, 5, 5, Neighbourhood::Cross);
+        assert_eq!(cross, vec![(4, 0), (1, 0), (0, 4), (0, 1)]);
+        assert_eq!(Topology::Rectangle.neighbours(2, 2, 5, 5, Neighbourhood::Radius2).len(), 24);
+        assert!(Neighbourhood::from_name("bishop").is_err());
+    }
+}
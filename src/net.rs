@@ -0,0 +1,538 @@
+use std::fmt;
+use std::io::{ BufRead, BufReader, Write };
+use std::net::{ TcpListener, TcpStream };
+use std::sync::{ mpsc::{ self, Receiver }, Mutex };
+use std::thread;
+use crate::layout::SeededGenerator;
+use crate::mine_core::{ BlockStatus, BlockType, ClickResult, MinePlayground, Position };
+use crate::shape::BoardShape;
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetMode {
+    /// everyone plays the same board
+    Coop,
+    /// everyone gets a board of their own, dealt from the same seed and
+    /// opened at the same block
+    Race,
+    /// watching the game of a player, see `Publisher`
+    Spectate,
+}
+
+/// Messages from a client, one a line, words split by spaces and positions
+/// written `X Y Z`. `join` comes first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    /// `join NAME`, the name without spaces
+    Join(String),
+    /// `reveal X Y Z`
+    Reveal(Position),
+    /// `flag X Y Z`, cycles the flags like a right click
+    Flag(Position),
+    /// `chord X Y Z`
+    Chord(Position),
+    /// `new` deals a new board to everyone
+    New,
+}
+
+/// What `welcome` tells a player joining.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Welcome {
+    pub id: usize,
+    pub mode: NetMode,
+    pub width: usize,
+    pub height: usize,
+    pub mine_count: usize,
+}
+
+/// Messages from the server, written like `ClientMessage`. Players are
+/// numbered from 0 in the order they joined.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// `welcome ID coop|race|spectate WIDTH HEIGHT MINES`, the answer to `join`,
+    /// or what spectators get first
+    Welcome(Welcome),
+    /// `player ID NAME`, for every player already there and every one joining
+    Player { id: usize, name: String },
+    /// `board`: a new board starts, every block hidden
+    Board,
+    /// `block X Y Z STATE`: a block of the player's board changed, STATE being
+    /// `hidden`, `question`, `flag N`, `open N`, 0 for spaces, or `mine N`
+    Block { pos: Position, btype: BlockType, bstatus: BlockStatus },
+    /// `progress ID SHOWN SAFE`: safe blocks opened on the board of a player,
+    /// in races
+    Progress { id: usize, shown: usize, safe: usize },
+    /// `result ID won|lost`: the board of a player ended, in co-op the one
+    /// everyone plays
+    Result { id: usize, won: bool },
+    /// `move MESSAGE`: what the player watched did, e.g. `move reveal 3 4 0`
+    Move(ClientMessage),
+    /// `cursor X Y Z`: the block under the mouse of the player watched
+    Cursor(Position),
+    /// `error TEXT`: a message the server didn't take
+    Error(String),
+}
+
+/// Who the server sends a message to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Recipient {
+    All,
+    Only(usize),
+}
+
+/// Everything the server knows: the players and their boards, one shared in
+/// co-op, one each in races.
+pub struct Room {
+    pub mode: NetMode,
+    pub width: usize,
+    pub height: usize,
+    pub mine_count: usize,
+    /// boards are seeded from it, `new` moves it on
+    pub seed: u64,
+    pub players: Vec<String>,
+    boards: Vec<MinePlayground>,
+    /// whether each board was won or lost
+    over: Vec<bool>,
+}
+
+/// The words of a position after the first word of a message.
+fn parse_pos(words: &[&str]) -> Result<Position, String> {
+    match words {
+        [x, y, z] => Ok(Position::new(parse_number(x)?, parse_number(y)?, parse_number(z)?)),
+        _ => Err(String::from("a position is written X Y Z")),
+    }
+}
+
+fn parse_number(word: &str) -> Result<usize, String> {
+    word.parse().map_err(|_| format!("`{}` is not a number", word))
+}
+
+impl NetMode {
+    pub fn name(self) -> &'static str {
+        match self {
+            NetMode::Coop => "coop",
+            NetMode::Race => "race",
+            NetMode::Spectate => "spectate",
+        }
+    }
+    pub fn from_name(name: &str) -> Result<NetMode, String> {
+        match name {
+            "coop" => Ok(NetMode::Coop),
+            "race" => Ok(NetMode::Race),
+            "spectate" => Ok(NetMode::Spectate),
+            _ => Err(format!("unknown mode `{}`, use coop, race or spectate", name)),
+        }
+    }
+}
+
+impl ClientMessage {
+    pub fn parse(line: &str) -> Result<ClientMessage, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["join", name] => Ok(ClientMessage::Join(name.to_string())),
+            ["reveal", pos @ ..] => Ok(ClientMessage::Reveal(parse_pos(pos)?)),
+            ["flag", pos @ ..] => Ok(ClientMessage::Flag(parse_pos(pos)?)),
+            ["chord", pos @ ..] => Ok(ClientMessage::Chord(parse_pos(pos)?)),
+            ["new"] => Ok(ClientMessage::New),
+            _ => Err(format!("unknown message `{}`", line.trim())),
+        }
+    }
+}
+impl fmt::Display for ClientMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientMessage::Join(name) => write!(f, "join {}", name),
+            ClientMessage::Reveal(pos) => write!(f, "reveal {} {} {}", pos.x, pos.y, pos.z),
+            ClientMessage::Flag(pos) => write!(f, "flag {} {} {}", pos.x, pos.y, pos.z),
+            ClientMessage::Chord(pos) => write!(f, "chord {} {} {}", pos.x, pos.y, pos.z),
+            ClientMessage::New => write!(f, "new"),
+        }
+    }
+}
+
+impl ServerMessage {
+    pub fn parse(line: &str) -> Result<ServerMessage, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let message = match words.as_slice() {
+            ["welcome", id, mode, width, height, mines] => ServerMessage::Welcome(Welcome {
+                id: parse_number(id)?,
+                mode: NetMode::from_name(mode)?,
+                width: parse_number(width)?,
+                height: parse_number(height)?,
+                mine_count: parse_number(mines)?,
+            }),
+            ["player", id, name] => ServerMessage::Player { id: parse_number(id)?, name: name.to_string() },
+            ["board"] => ServerMessage::Board,
+            ["block", x, y, z, state @ ..] => {
+                let (btype, bstatus) = match state {
+                    ["hidden"] => (BlockType::Space, BlockStatus::Hidden),
+                    ["question"] => (BlockType::Space, BlockStatus::QuestionMarked),
+                    ["flag", count] => (BlockType::Space, BlockStatus::Flaged(parse_number(count)?)),
+                    ["open", "0"] => (BlockType::Space, BlockStatus::Shown),
+                    ["open", tip] => (BlockType::Tip(parse_number(tip)?), BlockStatus::Shown),
+                    ["mine", count] => (BlockType::Mine(parse_number(count)?), BlockStatus::Shown),
+                    _ => return Err(format!("bad block state in `{}`", line.trim())),
+                };
+                ServerMessage::Block { pos: parse_pos(&[x, y, z])?, btype, bstatus }
+            }
+            ["progress", id, shown, safe] => ServerMessage::Progress {
+                id: parse_number(id)?,
+                shown: parse_number(shown)?,
+                safe: parse_number(safe)?,
+            },
+            ["result", id, "won"] => ServerMessage::Result { id: parse_number(id)?, won: true },
+            ["result", id, "lost"] => ServerMessage::Result { id: parse_number(id)?, won: false },
+            ["move", message @ ..] => ServerMessage::Move(ClientMessage::parse(&message.join(" "))?),
+            ["cursor", pos @ ..] => ServerMessage::Cursor(parse_pos(pos)?),
+            ["error", ..] => ServerMessage::Error(line.trim()["error".len()..].trim().to_string()),
+            _ => return Err(format!("unknown message `{}`", line.trim())),
+        };
+        Ok(message)
+    }
+}
+impl fmt::Display for ServerMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ServerMessage::Welcome(welcome) => write!(f, "welcome {} {} {} {} {}",
+                welcome.id, welcome.mode.name(), welcome.width, welcome.height, welcome.mine_count),
+            ServerMessage::Player { id, name } => write!(f, "player {} {}", id, name),
+            ServerMessage::Board => write!(f, "board"),
+            ServerMessage::Block { pos, btype, bstatus } => {
+                write!(f, "block {} {} {} ", pos.x, pos.y, pos.z)?;
+                match (bstatus, btype) {
+                    (BlockStatus::Hidden, _) => write!(f, "hidden"),
+                    (BlockStatus::QuestionMarked, _) => write!(f, "question"),
+                    (BlockStatus::Flaged(count), _) => write!(f, "flag {}", count),
+                    (BlockStatus::Shown, BlockType::Space) => write!(f, "open 0"),
+                    (BlockStatus::Shown, BlockType::Tip(tip)) => write!(f, "open {}", tip),
+                    (BlockStatus::Shown, BlockType::Mine(count)) => write!(f, "mine {}", count),
+                }
+            }
+            ServerMessage::Progress { id, shown, safe } => write!(f, "progress {} {} {}", id, shown, safe),
+            ServerMessage::Result { id, won } => write!(f, "result {} {}", id, if *won { "won" } else { "lost" }),
+            ServerMessage::Move(message) => write!(f, "move {}", message),
+            ServerMessage::Cursor(pos) => write!(f, "cursor {} {} {}", pos.x, pos.y, pos.z),
+            ServerMessage::Error(text) => write!(f, "error {}", text),
+        }
+    }
+}
+
+/// A block as its player may see it, hidden blocks telling nothing of mines.
+pub fn block_message(mp: &MinePlayground, index: usize) -> ServerMessage {
+    let block = mp.block_at(index);
+    let btype = if block.bstatus == BlockStatus::Shown { block.btype } else { BlockType::Space };
+    ServerMessage::Block { pos: block.pos, btype, bstatus: block.bstatus }
+}
+
+impl Room {
+    pub fn new(mode: NetMode, width: usize, height: usize, mine_count: usize, seed: u64) -> Result<Room, String> {
+        if mode == NetMode::Spectate {
+            return Err(String::from("spectators watch a game, see --publish"));
+        }
+        let mut room = Room { mode, width, height, mine_count, seed, players: vec![], boards: vec![], over: vec![] };
+        // dealt once anyway, to check the size
+        let board = room.deal()?;
+        if mode == NetMode::Coop {
+            room.boards.push(board);
+            room.over.push(false);
+        }
+        Ok(room)
+    }
+    /// Adds a player, with the messages that bring them and the others up to
+    /// date.
+    pub fn join(&mut self, name: &str) -> Result<(usize, Vec<(Recipient, ServerMessage)>), String> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(String::from("names can't be empty or have spaces"));
+        }
+        if self.mode == NetMode::Race {
+            self.boards.push(self.deal()?);
+            self.over.push(false);
+        }
+        let id = self.players.len();
+        self.players.push(name.to_string());
+        let welcome = Welcome { id, mode: self.mode, width: self.width, height: self.height, mine_count: self.mine_count };
+        let mut out = vec![(Recipient::Only(id), ServerMessage::Welcome(welcome))];
+        for (other, other_name) in self.players.iter().enumerate().filter(|&(other, _)| other != id) {
+            out.push((Recipient::Only(id), ServerMessage::Player { id: other, name: other_name.clone() }));
+        }
+        out.push((Recipient::All, ServerMessage::Player { id, name: name.to_string() }));
+        out.push((Recipient::Only(id), ServerMessage::Board));
+        out.extend(self.shown_blocks(id));
+        if self.mode == NetMode::Race {
+            for other in 0..self.players.len() {
+                let recipient = if other == id { Recipient::All } else { Recipient::Only(id) };
+                out.push((recipient, self.progress(other)));
+            }
+        }
+        Ok((id, out))
+    }
+    /// Plays a message of player `id`, with the messages it brings.
+    pub fn handle(&mut self, id: usize, message: ClientMessage) -> Vec<(Recipient, ServerMessage)> {
+        let pos = match message {
+            ClientMessage::Join(_) => return vec![(Recipient::Only(id), ServerMessage::Error(String::from("already joined")))],
+            ClientMessage::New => return self.new_boards(),
+            ClientMessage::Reveal(pos) | ClientMessage::Flag(pos) | ClientMessage::Chord(pos) => pos,
+        };
+        let board = self.board_of(id);
+        if self.over[board] {
+            return vec![(Recipient::Only(id), ServerMessage::Error(String::from("the game is over, send `new`")))];
+        }
+        let mp = &mut self.boards[board];
+        if pos.x >= mp.shape.width || pos.y >= mp.shape.height || pos.z >= mp.shape.depth {
+            return vec![(Recipient::Only(id), ServerMessage::Error(String::from("outside of the board")))];
+        }
+        let before: Vec<BlockStatus> = (0..mp.shape.block_count()).map(|index| mp.block_at(index).bstatus).collect();
+        let result = match message {
+            ClientMessage::Reveal(pos) => {
+                // race boards start open, moving mines would tell them apart
+                if self.mode == NetMode::Coop && mp.shown_count == 0 && mp.block(&pos).btype.mine_count() > 0 {
+                    mp.fix(&pos);
+                }
+                mp.click(&pos)
+            }
+            ClientMessage::Flag(pos) => {
+                mp.right_click(&pos);
+                ClickResult::NothingHappened
+            }
+            _ => mp.chord(&pos),
+        };
+        let recipient = match self.mode {
+            NetMode::Race => Recipient::Only(id),
+            _ => Recipient::All,
+        };
+        let mut out: Vec<(Recipient, ServerMessage)> = (0..before.len())
+            .filter(|&index| mp.block_at(index).bstatus != before[index])
+            .map(|index| (recipient, block_message(mp, index)))
+            .collect();
+        if self.mode == NetMode::Race {
+            out.push((Recipient::All, self.progress(id)));
+        }
+        match result {
+            ClickResult::Win | ClickResult::Wasted => {
+                self.over[board] = true;
+                out.push((Recipient::All, ServerMessage::Result { id, won: matches!(result, ClickResult::Win) }));
+            }
+            _ => {}
+        }
+        out
+    }
+    fn board_of(&self, id: usize) -> usize {
+        match self.mode {
+            NetMode::Race => id,
+            _ => 0,
+        }
+    }
+    /// A board of the seed, race boards with their opening shown.
+    fn deal(&self) -> Result<MinePlayground, String> {
+        let mut mp = MinePlayground::generate(BoardShape::rectangle(self.width, self.height), &self.mine_count, &SeededGenerator(self.seed))?;
+        if self.mode == NetMode::Race {
+            if let Some(start) = opening(&mp) {
+                mp.click(&start);
+            }
+        }
+        Ok(mp)
+    }
+    /// The blocks of the board of player `id` that aren't hidden.
+    fn shown_blocks(&self, id: usize) -> Vec<(Recipient, ServerMessage)> {
+        let mp = &self.boards[self.board_of(id)];
+        (0..mp.shape.block_count())
+            .filter(|&index| mp.block_at(index).bstatus != BlockStatus::Hidden)
+            .map(|index| (Recipient::Only(id), block_message(mp, index)))
+            .collect()
+    }
+    fn new_boards(&mut self) -> Vec<(Recipient, ServerMessage)> {
+        self.seed = self.seed.wrapping_add(1);
+        // the size was checked when the room was made
+        for board in 0..self.boards.len() {
+            self.boards[board] = self.deal().unwrap();
+            self.over[board] = false;
+        }
+        let mut out = vec![(Recipient::All, ServerMessage::Board)];
+        for id in 0..self.players.len() {
+            out.extend(self.shown_blocks(id));
+        }
+        if self.mode == NetMode::Race {
+            out.extend((0..self.players.len()).map(|id| (Recipient::All, self.progress(id))));
+        }
+        out
+    }
+    fn progress(&self, id: usize) -> ServerMessage {
+        let mp = &self.boards[self.board_of(id)];
+        let safe = (0..mp.shape.block_count()).filter(|&index| mp.shape.mask[index] && mp.block_at(index).btype.mine_count() == 0).count();
+        ServerMessage::Progress { id, shown: mp.shown_count, safe }
+    }
+}
+
+/// The safe block nearest the centre, an empty one when there is one, so every
+/// race board of a seed opens the same way.
+fn opening(mp: &MinePlayground) -> Option<Position> {
+    let (cx, cy) = (mp.shape.width as i64 / 2, mp.shape.height as i64 / 2);
+    (0..mp.shape.block_count())
+        .filter(|&index| mp.shape.mask[index] && mp.block_at(index).btype.mine_count() == 0)
+        .map(|index| mp.shape.position(index))
+        .min_by_key(|pos| (mp.block(pos).btype != BlockType::Space, (pos.x as i64 - cx).pow(2) + (pos.y as i64 - cy).pow(2)))
+}
+
+/// A client's side of the connection. Messages are read on a thread of
+/// their own, so the game never waits for the server.
+pub struct Client {
+    pub welcome: Welcome,
+    writer: TcpStream,
+    inbox: Mutex<Receiver<ServerMessage>>,
+}
+
+impl Client {
+    /// Joins the server at `address`, e.g. `127.0.0.1:7878`, and waits for
+    /// its welcome.
+    pub fn connect(address: &str, name: &str) -> Result<Client, String> {
+        Client::open(address, Some(name))
+    }
+    /// Attaches to a game published at `address`, only to watch it.
+    pub fn watch(address: &str) -> Result<Client, String> {
+        Client::open(address, None)
+    }
+    fn open(address: &str, name: Option<&str>) -> Result<Client, String> {
+        let writer = TcpStream::connect(address).map_err(|e| format!("{}: {}", address, e))?;
+        let mut reader = BufReader::new(writer.try_clone().map_err(|e| e.to_string())?);
+        if let Some(name) = name {
+            writeln!(&writer, "{}", ClientMessage::Join(name.to_string())).map_err(|e| e.to_string())?;
+        }
+        let welcome = match read_message(&mut reader)? {
+            ServerMessage::Welcome(welcome) => welcome,
+            ServerMessage::Error(text) => return Err(text),
+            other => return Err(format!("expected a welcome, got `{}`", other)),
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || loop {
+            let message = read_message(&mut reader);
+            let closed = message.is_err();
+            if sender.send(message.unwrap_or_else(ServerMessage::Error)).is_err() || closed {
+                break;
+            }
+        });
+        Ok(Client { welcome, writer, inbox: Mutex::new(receiver) })
+    }
+    pub fn send(&self, message: &ClientMessage) -> Result<(), String> {
+        writeln!(&self.writer, "{}", message).map_err(|e| e.to_string())
+    }
+    /// The messages arrived since the last call.
+    pub fn poll(&self) -> Vec<ServerMessage> {
+        self.inbox.lock().unwrap().try_iter().collect()
+    }
+}
+
+/// Streams a game to spectators on localhost: the board as it changes, the
+/// moves and the cursor, in the messages of the server. Spectators only
+/// listen, they are welcomed with the whole board.
+pub struct Publisher {
+    joining: Mutex<Receiver<TcpStream>>,
+    watchers: Mutex<Vec<TcpStream>>,
+}
+
+impl Publisher {
+    pub fn listen(port: u16) -> Result<Publisher, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).map_err(|e| format!("port {}: {}", port, e))?;
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if sender.send(stream).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Publisher { joining: Mutex::new(receiver), watchers: Mutex::new(vec![]) })
+    }
+    /// Brings spectators arrived since the last call up to date with `mp`.
+    pub fn welcome(&self, mp: &MinePlayground, mine_count: usize) {
+        let welcome = Welcome { id: 0, mode: NetMode::Spectate, width: mp.width, height: mp.height, mine_count };
+        let mut messages = vec![ServerMessage::Welcome(welcome), ServerMessage::Board];
+        messages.extend((0..mp.shape.block_count())
+            .filter(|&index| mp.block_at(index).bstatus != BlockStatus::Hidden)
+            .map(|index| block_message(mp, index)));
+        let mut watchers = self.watchers.lock().unwrap();
+        for stream in self.joining.lock().unwrap().try_iter() {
+            if messages.iter().all(|message| writeln!(&stream, "{}", message).is_ok()) {
+                watchers.push(stream);
+            }
+        }
+    }
+    /// Spectators that can't be written to any more are dropped.
+    pub fn publish(&self, messages: &[ServerMessage]) {
+        if messages.is_empty() {
+            return;
+        }
+        self.watchers.lock().unwrap().retain(|stream| messages.iter().all(|message| writeln!(&*stream, "{}", message).is_ok()));
+    }
+}
+
+fn read_message(reader: &mut BufReader<TcpStream>) -> Result<ServerMessage, String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) => Err(String::from("the server closed the connection")),
+        Ok(_) => ServerMessage::parse(&line),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_messages() {
+        let messages = [
+            ServerMessage::Welcome(Welcome { id: 1, mode: NetMode::Race, width: 8, height: 8, mine_count: 10 }),
+            ServerMessage::Player { id: 0, name: String::from("ann") },
+            ServerMessage::Board,
+            ServerMessage::Block { pos: Position::new(1, 2, 0), btype: BlockType::Tip(3), bstatus: BlockStatus::Shown },
+            ServerMessage::Block { pos: Position::new(1, 2, 0), btype: BlockType::Space, bstatus: BlockStatus::Flaged(1) },
+            ServerMessage::Progress { id: 1, shown: 20, safe: 54 },
+            ServerMessage::Result { id: 0, won: false },
+            ServerMessage::Move(ClientMessage::Flag(Position::new(0, 7, 0))),
+            ServerMessage::Cursor(Position::new(4, 4, 0)),
+            ServerMessage::Error(String::from("outside of the board")),
+        ];
+        for message in messages.iter() {
+            assert_eq!(&ServerMessage::parse(&message.to_string()).unwrap(), message);
+        }
+        assert_eq!(ClientMessage::parse("chord 3 4 0"), Ok(ClientMessage::Chord(Position::new(3, 4, 0))));
+        assert_eq!(ClientMessage::parse(&ClientMessage::Join(String::from("bo")).to_string()), Ok(ClientMessage::Join(String::from("bo"))));
+        assert!(ClientMessage::parse("reveal 3 4").is_err());
+    }
+    #[test]
+    fn test_room() {
+        let mut room = Room::new(NetMode::Coop, 8, 8, 10, 7).unwrap();
+        let (ann, _) = room.join("ann").unwrap();
+        let (bo, out) = room.join("bo").unwrap();
+        assert_eq!(out[0].1, ServerMessage::Welcome(Welcome { id: bo, mode: NetMode::Coop, width: 8, height: 8, mine_count: 10 }));
+        assert!(out.contains(&(Recipient::All, ServerMessage::Player { id: bo, name: String::from("bo") })));
+        // a co-op move reaches everyone, and bo joining late sees it
+        let out = room.handle(ann, ClientMessage::Reveal(Position::new(0, 0, 0)));
+        assert!(!out.is_empty() && out.iter().all(|(recipient, _)| *recipient == Recipient::All));
+        let (_, late) = room.join("cy").unwrap();
+        let shown = late.iter().filter(|(_, message)| matches!(message, ServerMessage::Block { .. })).count();
+        assert_eq!(shown, room.boards[0].shown_count);
+        // races deal the same board to everyone, moves only go to their player
+        let mut race = Room::new(NetMode::Race, 8, 8, 10, 7).unwrap();
+        let (ann, _) = race.join("ann").unwrap();
+        let (bo, _) = race.join("bo").unwrap();
+        assert_eq!(race.boards[ann].mine_layout(), race.boards[bo].mine_layout());
+        assert!(race.boards[bo].shown_count > 0);
+        let hidden = (0..64).find(|&index| race.boards[bo].block_at(index).bstatus == BlockStatus::Hidden).unwrap();
+        let out = race.handle(bo, ClientMessage::Flag(race.boards[bo].shape.position(hidden)));
+        assert_eq!(out[0].0, Recipient::Only(bo));
+        let opened = race.boards[bo].shown_count;
+        assert!(matches!(out.last().unwrap().1, ServerMessage::Progress { id, shown, safe: 54 } if id == bo && shown == opened));
+        // a first click on a mine moves nothing, the boards stay the same
+        let layout = race.boards[ann].mine_layout();
+        let mine = layout.iter().position(|&is_mine| is_mine).unwrap();
+        let safe = (0..layout.len()).find(|&index| !layout[index] && index != hidden && race.boards[bo].block_at(index).bstatus == BlockStatus::Hidden).unwrap();
+        race.handle(ann, ClientMessage::Reveal(race.boards[ann].shape.position(mine)));
+        race.handle(bo, ClientMessage::Reveal(race.boards[bo].shape.position(safe)));
+        assert_eq!(race.boards[ann].mine_layout(), layout);
+        assert_eq!(race.boards[bo].mine_layout(), layout);
+        let out = race.handle(ann, ClientMessage::New);
+        assert_eq!(out[0], (Recipient::All, ServerMessage::Board));
+        assert_eq!(race.seed, 8);
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs;
+use crate::mine_core::{ BlockType, MinePlayground };
+use crate::shape::BoardShape;
+use crate::neighbourhood::Neighbourhood;
+use crate::solver::{ is_solvable, Knowledge };
+use crate::topology::Topology;
+
+/// A hand-made board that starts partly revealed, with exactly one way to
+/// place its mines.
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub shape: BoardShape,
+    /// indexed like `BoardShape::index`
+    pub layout: Vec<bool>,
+    pub shown: Vec<bool>,
+}
+
+/// Names of the solved puzzles, one a line in a plain text file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PuzzleProgress {
+    pub solved: Vec<String>,
+}
+
+impl Puzzle {
+    /// Rows from the top of the board: `0` to `8` are shown numbers, `-`
+    /// hidden safe blocks, `*` mines and `.` holes. The numbers must be right
+    /// and must leave a single solution.
+    pub fn from_rows(name: &str, rows: &[&str]) -> Result<Puzzle, String> {
+        let puzzle = Puzzle::read_rows(name, rows)?;
+        let mp = puzzle.playground()?;
+        if mp.shown_count == puzzle.shape.playable_count() - puzzle.mine_count() {
+            return Err(format!("puzzle `{}` has nothing left to find", name));
+        }
+        if Knowledge::from_playground(&mp).count_solutions(2) > 1 {
+            return Err(format!("puzzle `{}` has more than one solution", name));
+        }
+        Ok(puzzle)
+    }
+    /// Like `from_rows`, only checking the numbers.
+    pub fn read_rows(name: &str, rows: &[&str]) -> Result<Puzzle, String> {
+        let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+        let (mut mask, mut layout, mut shown, mut numbers) = (vec![], vec![], vec![], vec![]);
+        // row 0 is the bottom of the board
+        for row in rows.iter().rev() {
+            let mut cells: Vec<char> = row.chars().collect();
+            cells.resize(width, '.');
+            for c in cells {
+                match c {
+                    '.' | '-' | '*' => numbers.push(None),
+                    '0'..='8' => numbers.push(c.to_digit(10)),
+                    _ => return Err(format!("puzzle `{}`: unknown block `{}`", name, c)),
+                }
+                mask.push(c != '.');
+                layout.push(c == '*');
+                shown.push(c.is_ascii_digit());
+            }
+        }
+        let puzzle = Puzzle { name: String::from(name), shape: BoardShape::with_mask(width, rows.len(), Topology::Rectangle, mask), layout, shown };
+        let mp = puzzle.playground()?;
+        for (index, number) in numbers.into_iter().enumerate() {
+            let tip = match mp.block_at(index).btype {
+                BlockType::Tip(val) => val,
+                _ => 0,
+            };
+            if number.is_some_and(|number| number as usize != tip) {
+                let pos = puzzle.shape.position(index);
+                return Err(format!("puzzle `{}`: the block at ({}, {}) should show {}", name, pos.x, pos.y, tip));
+            }
+        }
+        Ok(puzzle)
+    }
+    pub fn playground(&self) -> Result<MinePlayground, String> {
+        let mut mp = MinePlayground::from_layout(self.shape.clone(), &self.layout)?;
+        for index in (0..self.shown.len()).filter(|&index| self.shown[index]) {
+            mp.show(&self.shape.position(index))?;
+        }
+        Ok(mp)
+    }
+    pub fn mine_count(&self) -> usize {
+        self.layout.iter().filter(|&&is_mine| is_mine).count()
+    }
+    /// The board as the editor shows it: every number open, the mines flagged.
+    pub fn preview(&self) -> Result<MinePlayground, String> {
+        let mut mp = self.playground()?;
+        for index in (0..self.layout.len()).filter(|&index| self.shape.mask[index]) {
+            let pos = self.shape.position(index);
+            if self.layout[index] {
+                mp.right_click(&pos);
+            } else {
+                mp.show(&pos)?;
+            }
+        }
+        Ok(mp)
+    }
+    /// Puts or removes a mine, a mine never starts revealed. Returns whether
+    /// the board changed.
+    pub fn toggle_mine(&mut self, index: usize) -> bool {
+        if !self.shape.mask[index] {
+            return false;
+        }
+        self.layout[index] = !self.layout[index];
+        self.shown[index] = false;
+        true
+    }
+    /// Picks whether a safe block starts revealed.
+    pub fn toggle_shown(&mut self, index: usize) -> bool {
+        if !self.shape.mask[index] || self.layout[index] {
+            return false;
+        }
+        self.shown[index] = !self.shown[index];
+        true
+    }
+    /// Whether the board can be finished from its revealed blocks without a
+    /// single guess, which also makes its solution unique.
+    pub fn check(&self) -> Result<(), String> {
+        if !self.shown.contains(&true) {
+            return Err(String::from("no block starts revealed"));
+        }
+        if !is_solvable(&self.playground()?) {
+            return Err(String::from("the board needs a guess"));
+        }
+        Ok(())
+    }
+    /// The rows `from_rows` reads, only for flat rectangles with the classic
+    /// neighbourhood.
+    pub fn to_rows(&self) -> Result<Vec<String>, String> {
+        let shape = &self.shape;
+        if shape.topology != Topology::Rectangle || shape.neighbourhood != Neighbourhood::Classic || shape.depth > 1 {
+            return Err(String::from("only flat rectangles with classic neighbours can be written as rows"));
+        }
+        let mp = self.playground()?;
+        Ok((0..shape.height).rev().map(|y| (0..shape.width).map(|x| {
+            let index = y * shape.width + x;
+            match mp.block_at(index).btype {
+                _ if !shape.mask[index] => '.',
+                _ if self.layout[index] => '*',
+                BlockType::Tip(val) if self.shown[index] => std::char::from_digit(val as u32, 10).unwrap(),
+                _ if self.shown[index] => '0',
+                _ => '-',
+            }
+        }).collect()).collect())
+    }
+}
+
+/// Reads a pack of puzzles: `name: ...` starts a puzzle and its rows follow,
+/// lines starting with `#` are comments.
+pub fn read_pack(text: &str) -> Result<Vec<Puzzle>, String> {
+    let mut puzzles = vec![];
+    let mut current: Option<(&str, Vec<&str>)> = None;
+    for line in text.lines().map(str::trim_end).filter(|line| !line.trim().is_empty() && !line.starts_with('#')) {
+        if let Some(name) = line.strip_prefix("name:") {
+            if let Some((name, rows)) = current.take() {
+                puzzles.push(Puzzle::from_rows(name, &rows)?);
+            }
+            current = Some((name.trim(), vec![]));
+            continue;
+        }
+        match current.as_mut() {
+            Some((_, rows)) => rows.push(line),
+            None => return Err(String::from("puzzle pack: rows before the first name")),
+        }
+    }
+    if let Some((name, rows)) = current {
+        puzzles.push(Puzzle::from_rows(name, &rows)?);
+    }
+    Ok(puzzles)
+}
+
+impl PuzzleProgress {
+    /// No progress when the file doesn't exist yet.
+    pub fn load(path: &str) -> PuzzleProgress {
+        let text = fs::read_to_string(path).unwrap_or_default();
+        PuzzleProgress { solved: text.lines().map(str::trim).filter(|line| !line.is_empty()).map(String::from).collect() }
+    }
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.solved.iter().map(|name| format!("{}\n", name)).collect::<String>()).map_err(|e| e.to_string())
+    }
+    pub fn is_solved(&self, name: &str) -> bool {
+        self.solved.iter().any(|cur| cur == name)
+    }
+    pub fn mark_solved(&mut self, name: &str) {
+        if !self.is_solved(name) {
+            self.solved.push(String::from(name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_read_pack() {
+        let pack = "# two mines in a corner\nname: corner\n*-100\n-*100\n11100\n00000\n00000\n";
+        let puzzles = read_pack(pack).unwrap();
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(puzzles[0].mine_count(), 2);
+        let mp = puzzles[0].playground().unwrap();
+        assert_eq!(mp.shown_count, 21);
+        // the mine could be anywhere away from the 0
+        assert!(read_pack("name: anywhere\n*----\n-----\n-----\n-----\n----0\n").is_err());
+        assert!(read_pack("name: wrong number\n*-100\n-*100\n22100\n00000\n00000\n").is_err());
+    }
+    #[test]
+    fn test_editing() {
+        let mut puzzle = read_pack("name: corner\n*-100\n-*100\n11100\n00000\n00000\n").unwrap().remove(0);
+        assert_eq!(puzzle.check(), Ok(()));
+        assert_eq!(puzzle.to_rows().unwrap(), vec!["*-100", "-*100", "11100", "00000", "00000"]);
+        let mp = puzzle.preview().unwrap();
+        assert_eq!(mp.shown_count, 23);
+        assert_eq!(mp.block_at(20).bstatus, crate::mine_core::BlockStatus::Flaged(1));
+        // a mine on the top right 0 turns its neighbours into 1s
+        assert!(puzzle.toggle_mine(24));
+        assert!(!puzzle.toggle_shown(24));
+        assert_eq!(puzzle.to_rows().unwrap(), vec!["*-11*", "-*111", "11100", "00000", "00000"]);
+        // only the 1 left of it shown, its mine could be anywhere around
+        for index in 0..25 {
+            if index != 22 && puzzle.shown[index] {
+                assert!(puzzle.toggle_shown(index));
+            }
+        }
+        assert_eq!(puzzle.check(), Err(String::from("the board needs a guess")));
+    }
+    #[test]
+    fn test_shipped_pack() {
+        let puzzles = read_pack(include_str!("../assets/puzzles.txt")).unwrap();
+        assert!(puzzles.len() >= 10);
+        for (i, puzzle) in puzzles.iter().enumerate() {
+            assert!(puzzles[i + 1..].iter().all(|cur| cur.name != puzzle.name));
+        }
+    }
+}
@@ -0,0 +1,137 @@
+/// Seconds on the clock when survival starts.
+pub const SURVIVAL_START: f32 = 60.0;
+/// Seconds a survival win adds for every mine of the board won.
+pub const SURVIVAL_BONUS: f32 = 2.0;
+/// Mines added to the next board after every survival win.
+pub const SURVIVAL_STEP: usize = 2;
+/// Survival boards stop getting denser at this share of mines.
+pub const MAX_DENSITY: f32 = 0.35;
+
+/// Games of many boards against one clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionMode {
+    /// as many boards as possible before that many seconds run out, a lost
+    /// board only costs time
+    TimeAttack(f32),
+    /// boards until one is lost or the clock runs out, every win makes the next
+    /// board denser and adds time
+    Survival,
+}
+
+impl SessionMode {
+    /// `time-attack:MINUTES` or `survival`.
+    pub fn from_name(name: &str) -> Result<SessionMode, String> {
+        if let Some(minutes) = name.strip_prefix("time-attack:") {
+            return match minutes.parse::<u32>() {
+                Ok(minutes) if minutes > 0 => Ok(SessionMode::TimeAttack(minutes as f32 * 60.0)),
+                _ => Err(format!("time attack takes a number of minutes, not {}", minutes)),
+            };
+        }
+        match name {
+            "survival" => Ok(SessionMode::Survival),
+            _ => Err(format!("unknown session {}, sessions are time-attack:MINUTES and survival", name)),
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionMode::TimeAttack(_) => "time attack",
+            SessionMode::Survival => "survival",
+        }
+    }
+}
+
+/// The boards of one session and its clock, which runs from board to board.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub mode: SessionMode,
+    pub time_left: f32,
+    /// seconds played in the whole session
+    pub elapsed: f32,
+    pub won: usize,
+    pub lost: usize,
+    /// mines of the board being played
+    pub mine_count: usize,
+    /// seconds spent on the boards won
+    won_time: f32,
+    block_count: usize,
+    pub over: bool,
+}
+
+impl Session {
+    /// Starts with boards of `block_count` blocks and `mine_count` mines.
+    pub fn new(mode: SessionMode, mine_count: usize, block_count: usize) -> Session {
+        let time_left = match mode {
+            SessionMode::TimeAttack(seconds) => seconds,
+            SessionMode::Survival => SURVIVAL_START,
+        };
+        Session { mode, time_left, elapsed: 0.0, won: 0, lost: 0, mine_count, won_time: 0.0, block_count, over: false }
+    }
+    /// Runs the clock, true when it runs out now.
+    pub fn tick(&mut self, seconds: f32) -> bool {
+        if self.over {
+            return false;
+        }
+        let seconds = seconds.min(self.time_left);
+        self.time_left -= seconds;
+        self.elapsed += seconds;
+        self.over = self.time_left <= 0.0;
+        self.over
+    }
+    /// A board won in `seconds`.
+    pub fn board_won(&mut self, seconds: f32) {
+        self.won += 1;
+        self.won_time += seconds;
+        if self.mode == SessionMode::Survival {
+            self.time_left += self.mine_count as f32 * SURVIVAL_BONUS;
+            let max_mines = (self.block_count as f32 * MAX_DENSITY) as usize;
+            self.mine_count = max_mines.min(self.mine_count + SURVIVAL_STEP).max(self.mine_count);
+        }
+    }
+    pub fn board_lost(&mut self) {
+        self.lost += 1;
+        if self.mode == SessionMode::Survival {
+            self.over = true;
+        }
+    }
+    /// `2:31`, the time left.
+    pub fn clock(&self) -> String {
+        let seconds = self.time_left.ceil() as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+    pub fn summary(&self) -> String {
+        let average = if self.won > 0 { format!(", {:.1}s a board", self.won_time / self.won as f32) } else { String::new() };
+        match self.mode {
+            SessionMode::TimeAttack(_) => format!("{} boards cleared, {} lost{}", self.won, self.lost, average),
+            SessionMode::Survival => format!("survived {} boards up to {} mines in {:.0}s{}", self.won, self.mine_count, self.elapsed, average),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_session() {
+        assert_eq!(SessionMode::from_name("time-attack:3"), Ok(SessionMode::TimeAttack(180.0)));
+        assert_eq!(SessionMode::from_name("survival"), Ok(SessionMode::Survival));
+        assert!(SessionMode::from_name("time-attack:0").is_err());
+        let mut session = Session::new(SessionMode::TimeAttack(60.0), 10, 64);
+        session.board_won(20.0);
+        session.board_lost();
+        assert!(!session.tick(30.5));
+        assert_eq!((session.clock(), session.mine_count, session.over), (String::from("0:30"), 10, false));
+        assert!(session.tick(40.0));
+        assert_eq!(session.elapsed, 60.0);
+        assert_eq!(session.summary(), "1 boards cleared, 1 lost, 20.0s a board");
+        // 22 mines at most on 64 blocks
+        let mut session = Session::new(SessionMode::Survival, 18, 64);
+        session.board_won(10.0);
+        assert_eq!((session.mine_count, session.time_left), (20, 96.0));
+        session.board_won(10.0);
+        session.board_won(10.0);
+        assert_eq!(session.mine_count, 22);
+        session.board_lost();
+        assert!(session.over);
+        assert!(!session.tick(1.0));
+    }
+}
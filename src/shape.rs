@@ -0,0 +1,184 @@
+use std::fs;
+use crate::mine_core::Position;
+use crate::neighbourhood::Neighbourhood;
+use crate::topology::Topology;
+
+/// Everything about a board except its mines: size, how blocks connect and
+/// which blocks are cut out of it.
+#[derive(Debug, Clone)]
+pub struct BoardShape {
+    pub width: usize,
+    pub height: usize,
+    /// number of stacked layers, 1 for flat boards
+    pub depth: usize,
+    pub topology: Topology,
+    pub neighbourhood: Neighbourhood,
+    /// indexed like `index`, `false` for blocks that are not part of the board
+    pub mask: Vec<bool>,
+}
+
+impl BoardShape {
+    pub fn new(width: usize, height: usize, topology: Topology) -> BoardShape {
+        BoardShape::with_mask(width, height, topology, vec![true; width * height])
+    }
+    /// A flat shape with `mask` indexed like `index`.
+    pub fn with_mask(width: usize, height: usize, topology: Topology, mask: Vec<bool>) -> BoardShape {
+        BoardShape { width, height, depth: 1, topology, neighbourhood: Neighbourhood::Classic, mask }
+    }
+    pub fn rectangle(width: usize, height: usize) -> BoardShape {
+        BoardShape::new(width, height, Topology::Rectangle)
+    }
+    /// Builds a shape from its name: `full`, `cross`, `donut`, `heart`, or
+    /// `file:PATH` for a text mask (`.` and spaces are holes) or an ASCII PBM
+    /// image (black pixels are blocks). File masks bring their own size.
+    pub fn from_name(name: &str, width: usize, height: usize, topology: Topology) -> Result<BoardShape, String> {
+        if let Some(path) = name.strip_prefix("file:") {
+            let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            let (width, height, mask) = if path.ends_with(".pbm") { read_pbm(&text)? } else { read_text_mask(&text)? };
+            return Ok(BoardShape::with_mask(width, height, topology, mask));
+        }
+        let (cx, cy) = ((width as f32 - 1.0) / 2.0, (height as f32 - 1.0) / 2.0);
+        let inside: Box<dyn Fn(f32, f32) -> bool> = match name {
+            "full" => Box::new(|_, _| true),
+            "cross" => Box::new(|dx, dy| dx.abs() <= 1.0 / 6.0 || dy.abs() <= 1.0 / 6.0),
+            "donut" => Box::new(|dx, dy| (0.2..=0.5).contains(&(dx * dx + dy * dy).sqrt())),
+            "heart" => Box::new(|dx, dy| {
+                let (x, y) = (dx * 2.6, dy * 2.6 + 0.2);
+                (x * x + y * y - 1.0).powi(3) - x * x * y.powi(3) <= 0.0
+            }),
+            _ => return Err(format!("unknown board shape `{}`", name)),
+        };
+        // offsets from the centre, scaled so the board spans -0.5..0.5
+        let mask = (0..width * height).map(|i| {
+            inside(((i % width) as f32 - cx) / width as f32, ((i / width) as f32 - cy) / height as f32)
+        }).collect();
+        Ok(BoardShape::with_mask(width, height, topology, mask))
+    }
+    /// Stacks `depth` copies of a flat shape. Every block then also touches the
+    /// block right above or below it and that block's neighbours, so a full
+    /// rectangle gets 26 neighbours.
+    pub fn with_depth(self, depth: usize) -> BoardShape {
+        let mask = self.mask.iter().copied().cycle().take(self.mask.len() * depth).collect();
+        BoardShape { depth, mask, ..self }
+    }
+    /// Hex boards only have their touching blocks.
+    pub fn with_neighbourhood(self, neighbourhood: Neighbourhood) -> Result<BoardShape, String> {
+        if self.topology == Topology::Hex && neighbourhood != Neighbourhood::Classic {
+            return Err(format!("hex boards can't use the {} neighbourhood", neighbourhood.name()));
+        }
+        Ok(BoardShape { neighbourhood, ..self })
+    }
+    pub fn block_count(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+    pub fn index(&self, pos: &Position) -> usize {
+        (pos.z * self.height + pos.y) * self.width + pos.x
+    }
+    pub fn position(&self, index: usize) -> Position {
+        let layer = self.width * self.height;
+        Position { x: index % self.width, y: index % layer / self.width, z: index / layer }
+    }
+    pub fn is_playable(&self, pos: &Position) -> bool {
+        self.mask[self.index(pos)]
+    }
+    pub fn playable_count(&self) -> usize {
+        self.mask.iter().filter(|&&playable| playable).count()
+    }
+    /// Neighbours given by the topology, minus the ones cut out of the board.
+    pub fn neighbours(&self, pos: &Position) -> Vec<Position> {
+        let flat = self.topology.neighbours(pos.x, pos.y, self.width, self.height, self.neighbourhood);
+        let mut r: Vec<Position> = flat.iter().map(|&(x, y)| Position { x, y, z: pos.z }).collect();
+        for &z in [pos.z.wrapping_sub(1), pos.z + 1].iter() {
+            if z < self.depth {
+                r.push(Position { z, ..*pos });
+                r.extend(flat.iter().map(|&(x, y)| Position { x, y, z }));
+            }
+        }
+        r.retain(|cur| self.is_playable(cur));
+        r
+    }
+    pub fn max_neighbours(&self) -> usize {
+        let flat = self.topology.max_neighbours(self.neighbourhood);
+        match self.depth {
+            1 => flat,
+            2 => flat * 2 + 1,
+            _ => flat * 3 + 2,
+        }
+    }
+    /// Whether a block lies on an outline, inner or outer.
+    pub fn is_edge(&self, pos: &Position) -> bool {
+        self.neighbours(pos).len() < self.max_neighbours()
+    }
+}
+
+/// Rows of text, first line at the top of the board.
+fn read_text_mask(text: &str) -> Result<(usize, usize, Vec<bool>), String> {
+    let rows: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    let width = rows.iter().map(|row| row.chars().count()).max().unwrap_or(0);
+    let mut mask = vec![];
+    // row 0 is the bottom of the board
+    for row in rows.iter().rev() {
+        let mut cells: Vec<bool> = row.chars().map(|c| c != '.' && c != ' ').collect();
+        cells.resize(width, false);
+        mask.extend(cells);
+    }
+    Ok((width, rows.len(), mask))
+}
+
+/// Plain (P1) portable bitmap.
+fn read_pbm(text: &str) -> Result<(usize, usize, Vec<bool>), String> {
+    let mut tokens = text.lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split_whitespace());
+    if tokens.next() != Some("P1") {
+        return Err(String::from("pbm: only plain P1 bitmaps are supported"));
+    }
+    let mut size = || tokens.next().and_then(|token| token.parse::<usize>().ok()).ok_or("pbm: bad size");
+    let (width, height) = (size()?, size()?);
+    let pixels: Vec<bool> = tokens.flat_map(|token| token.chars()).map(|c| c == '1').collect();
+    if pixels.len() != width * height {
+        return Err(String::from("pbm: pixel count does not match the size"));
+    }
+    Ok((width, height, pixels.chunks(width).rev().flatten().copied().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_named_shapes() {
+        for name in ["full", "cross", "donut", "heart"].iter() {
+            let shape = BoardShape::from_name(name, 16, 16, Topology::Rectangle).unwrap();
+            assert!(shape.playable_count() > 16 * 16 / 4);
+        }
+        let donut = BoardShape::from_name("donut", 16, 16, Topology::Rectangle).unwrap();
+        assert!(!donut.is_playable(&Position::new(8, 8, 0)));
+        assert!(!donut.is_playable(&Position::new(0, 0, 0)));
+        assert!(donut.neighbours(&Position::new(8, 1, 0)).iter().all(|cur| donut.is_playable(cur)));
+        assert!(BoardShape::from_name("blob", 16, 16, Topology::Rectangle).is_err());
+    }
+    #[test]
+    fn test_layers() {
+        let shape = BoardShape::rectangle(5, 6).with_depth(4);
+        assert_eq!(shape.block_count(), 120);
+        let pos = Position::new(4, 2, 3);
+        assert_eq!(shape.position(shape.index(&pos)), pos);
+        assert_eq!(shape.neighbours(&Position::new(2, 2, 1)).len(), 26);
+        assert_eq!(shape.neighbours(&Position::new(0, 0, 0)).len(), 7);
+        assert!(shape.is_edge(&Position::new(2, 2, 3)));
+    }
+    #[test]
+    fn test_neighbourhood_shapes() {
+        let shape = BoardShape::rectangle(9, 9).with_neighbourhood(Neighbourhood::Knight).unwrap();
+        assert_eq!(shape.neighbours(&Position::new(4, 4, 0)).len(), 8);
+        assert!(shape.is_edge(&Position::new(1, 4, 0)));
+        assert!(!shape.is_edge(&Position::new(2, 2, 0)));
+        assert!(BoardShape::new(9, 9, Topology::Hex).with_neighbourhood(Neighbourhood::Cross).is_err());
+    }
+    #[test]
+    fn test_read_masks() {
+        assert_eq!(read_text_mask("##.\n#\n").unwrap(), (3, 2, vec![true, false, false, true, true, false]));
+        assert_eq!(read_pbm("P1\n# comment\n3 2\n1 1 0\n100\n").unwrap(), (3, 2, vec![true, false, false, true, true, false]));
+        assert!(read_pbm("P4\n3 2\n").is_err());
+    }
+}
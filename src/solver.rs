@@ -0,0 +1,540 @@
+use std::collections::HashSet;
+use crate::mine_core::{ BlockStatus, BlockType, MinePlayground };
+use crate::shape::BoardShape;
+
+/// Steps of the search through the layouts around the numbers before
+/// `mine_probabilities` settles for a rough estimate.
+const EXACT_STEPS: usize = 200_000;
+
+/// What a player can know about a block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cell {
+    Hidden,
+    Shown(usize),
+    Mine,
+    /// cut out of the board
+    Void,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deduction {
+    pub index: usize,
+    pub is_mine: bool,
+}
+/// Which rule a deduction comes from, numbers given by index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reason {
+    /// the number has no mines left to place, or as many as hidden neighbours
+    Single(usize),
+    /// the hidden neighbours of the first number all touch the second one
+    Subset(usize, usize),
+    /// no mines are left, or as many as hidden blocks
+    MineCount,
+}
+
+/// The visible state of a board, flattened to `BoardShape::index` so the
+/// deduction rules don't care about the board shape.
+#[derive(Debug, Clone)]
+pub struct Knowledge {
+    pub shape: BoardShape,
+    pub cells: Vec<Cell>,
+    pub neighbours: Vec<Vec<usize>>,
+    pub mine_count: usize,
+}
+
+impl Knowledge {
+    /// Flags are the player's opinion, not knowledge, so they are read as hidden.
+    /// Assumes one mine per block, see `LayoutGenerator::allows_stacked_mines`.
+    pub fn from_playground(mp: &MinePlayground) -> Knowledge {
+        let mut cells = vec![];
+        let mut neighbours = vec![];
+        let mut mine_count = 0;
+        for index in 0..mp.shape.block_count() {
+            let block = mp.block_at(index);
+            if let BlockType::Mine(_) = block.btype {
+                mine_count += 1;
+            }
+            if !mp.shape.mask[index] {
+                cells.push(Cell::Void);
+                neighbours.push(vec![]);
+                continue;
+            }
+            cells.push(match (&block.bstatus, &block.btype) {
+                (BlockStatus::Shown, BlockType::Mine(_)) => Cell::Mine,
+                (BlockStatus::Shown, BlockType::Tip(val)) => Cell::Shown(*val),
+                (BlockStatus::Shown, BlockType::Space) => Cell::Shown(0),
+                _ => Cell::Hidden,
+            });
+            neighbours.push(mp.neighbour_indices(index).collect());
+        }
+        Knowledge { shape: mp.shape.clone(), cells, neighbours, mine_count }
+    }
+    /// Every block whose content follows from the visible numbers, using the
+    /// single-number rule, the subset rule between two numbers and the total
+    /// mine count.
+    pub fn deduce(&self) -> Vec<Deduction> {
+        self.deduce_with_reasons().into_iter().map(|(deduction, _)| deduction).collect()
+    }
+    /// Like `deduce`, with the rule behind every deduction.
+    pub fn deduce_with_reasons(&self) -> Vec<(Deduction, Reason)> {
+        let mut found: Vec<(Deduction, Reason)> = vec![];
+        let mut seen = HashSet::new();
+        let mut push = |found: &mut Vec<(Deduction, Reason)>, index: usize, is_mine: bool, reason: Reason| {
+            if seen.insert(index) {
+                found.push((Deduction { index, is_mine }, reason));
+            }
+        };
+        let constraints = self.constraints();
+        for (number, hidden, mines) in constraints.iter() {
+            if *mines == 0 || *mines == hidden.len() {
+                for &index in hidden.iter() {
+                    push(&mut found, index, *mines != 0, Reason::Single(*number));
+                }
+            }
+        }
+        for (small_number, small, small_mines) in constraints.iter() {
+            for (big_number, big, big_mines) in constraints.iter() {
+                if small.len() >= big.len() || !small.iter().all(|index| big.contains(index)) {
+                    continue;
+                }
+                let rest: Vec<usize> = big.iter().filter(|index| !small.contains(index)).copied().collect();
+                let rest_mines = big_mines - small_mines.min(big_mines);
+                if *big_mines == *small_mines || rest_mines == rest.len() {
+                    for index in rest {
+                        push(&mut found, index, *big_mines != *small_mines, Reason::Subset(*small_number, *big_number));
+                    }
+                }
+            }
+        }
+        let hidden: Vec<usize> = (0..self.cells.len()).filter(|&i| self.cells[i] == Cell::Hidden).collect();
+        let mines_left = self.mines_left();
+        if !hidden.is_empty() && (mines_left == 0 || mines_left == hidden.len()) {
+            for index in hidden {
+                push(&mut found, index, mines_left != 0, Reason::MineCount);
+            }
+        }
+        found
+    }
+    /// Mines that aren't shown yet.
+    pub fn mines_left(&self) -> usize {
+        self.mine_count.saturating_sub(self.cells.iter().filter(|&&cell| cell == Cell::Mine).count())
+    }
+    /// The chance of holding a mine for every block, 1 for blocks that aren't
+    /// hidden: the share of the layouts `count_solutions` finds with a mine
+    /// there. Past `EXACT_STEPS` it is only estimated, see
+    /// `rough_probabilities`.
+    pub fn mine_probabilities(&self) -> Vec<f64> {
+        self.exact_probabilities().unwrap_or_else(|| self.rough_probabilities())
+    }
+    /// Every layout of the blocks next to numbers counts for the ways to
+    /// spread the mines left over the blocks away from them. `None` when
+    /// there are too many layouts, or none.
+    fn exact_probabilities(&self) -> Option<Vec<f64>> {
+        if self.is_broken() {
+            return None;
+        }
+        let (frontier, mut counter) = self.solution_counter(usize::MAX);
+        counter.steps = EXACT_STEPS;
+        counter.tally = Some(vec![(0.0, vec![0.0; frontier.len()]); frontier.len().min(counter.mines_left) + 1]);
+        counter.search(0, 0);
+        if counter.steps == 0 {
+            return None;
+        }
+        let (free, mines_left) = (counter.free, counter.mines_left);
+        let tally = counter.tally.unwrap();
+        // C(free, mines away from numbers) for every count of mines next to
+        // them, relative to the largest so that it fits an f64
+        let ln_ways: Vec<Option<f64>> = tally.iter().enumerate()
+            .map(|(placed, (layouts, _))| if *layouts > 0.0 { Some(ln_binomial(free, mines_left - placed)) } else { None })
+            .collect();
+        let most = ln_ways.iter().flatten().copied().fold(f64::NEG_INFINITY, f64::max);
+        let ways: Vec<f64> = ln_ways.iter().map(|ln| ln.map_or(0.0, |ln| (ln - most).exp())).collect();
+        let total: f64 = tally.iter().zip(ways.iter()).map(|((layouts, _), ways)| layouts * ways).sum();
+        if total == 0.0 {
+            return None;
+        }
+        let away: f64 = tally.iter().zip(ways.iter()).enumerate()
+            .map(|(placed, ((layouts, _), ways))| layouts * ways * (mines_left - placed) as f64)
+            .sum::<f64>() / (total * free.max(1) as f64);
+        let mut odds: Vec<f64> = self.cells.iter().map(|&cell| if cell == Cell::Hidden { away } else { 1.0 }).collect();
+        for (pos, &index) in frontier.iter().enumerate() {
+            odds[index] = tally.iter().zip(ways.iter()).map(|((_, mines), ways)| mines[pos] * ways).sum::<f64>() / total;
+        }
+        Some(odds)
+    }
+    /// A rough chance of holding a mine for every block, 1 for blocks that
+    /// aren't hidden: the worst estimate from the numbers around a block, or
+    /// the density of the mines left away from numbers.
+    fn rough_probabilities(&self) -> Vec<f64> {
+        let hidden = self.cells.iter().filter(|&&cell| cell == Cell::Hidden).count();
+        let density = self.mines_left() as f64 / hidden.max(1) as f64;
+        let mut odds: Vec<Option<f64>> = vec![None; self.cells.len()];
+        for (_, cells, mines) in self.constraints() {
+            let chance = mines as f64 / cells.len() as f64;
+            for index in cells {
+                odds[index] = Some(odds[index].map_or(chance, |cur| cur.max(chance)));
+            }
+        }
+        (0..self.cells.len()).map(|index| match self.cells[index] {
+            Cell::Hidden => odds[index].unwrap_or(density),
+            _ => 1.0,
+        }).collect()
+    }
+    /// How many mine layouts agree with the shown numbers and the mine count,
+    /// counting no further than `limit`. Blocks away from the numbers only
+    /// add the ways to spread the mines left over them.
+    pub fn count_solutions(&self, limit: usize) -> usize {
+        if self.is_broken() {
+            return 0;
+        }
+        let (_, mut counter) = self.solution_counter(limit);
+        counter.search(0, 0);
+        counter.found.min(limit)
+    }
+    /// The hidden blocks next to numbers, and the search through their mines.
+    fn solution_counter(&self, limit: usize) -> (Vec<usize>, SolutionCounter) {
+        let constraints = self.constraints();
+        let mut frontier: Vec<usize> = vec![];
+        let mut touching: Vec<Vec<usize>> = vec![];
+        for (id, (_, hidden, _)) in constraints.iter().enumerate() {
+            for &index in hidden.iter() {
+                match frontier.iter().position(|&cur| cur == index) {
+                    Some(pos) => touching[pos].push(id),
+                    None => {
+                        frontier.push(index);
+                        touching.push(vec![id]);
+                    }
+                }
+            }
+        }
+        let hidden = self.cells.iter().filter(|&&cell| cell == Cell::Hidden).count();
+        let counter = SolutionCounter {
+            remaining: constraints.iter().map(|(_, _, mines)| *mines).collect(),
+            unassigned: constraints.iter().map(|(_, hidden, _)| hidden.len()).collect(),
+            free: hidden - frontier.len(),
+            mines_left: self.mines_left(),
+            limit,
+            found: 0,
+            mines: vec![false; frontier.len()],
+            tally: None,
+            steps: usize::MAX,
+            touching,
+        };
+        (frontier, counter)
+    }
+    /// Every hidden block that is the same in all the layouts `count_solutions`
+    /// finds. Slower than `deduce`, but it misses nothing, e.g. the 1-2-1
+    /// pattern, which no two numbers solve alone.
+    pub fn certain(&self) -> Vec<Deduction> {
+        (0..self.cells.len())
+            .filter(|&index| self.cells[index] == Cell::Hidden)
+            .filter_map(|index| match (self.proves(index, true), self.proves(index, false)) {
+                (true, _) => Some(Deduction { index, is_mine: true }),
+                (_, true) => Some(Deduction { index, is_mine: false }),
+                _ => None,
+            })
+            .collect()
+    }
+    /// Whether no layout disagrees that the hidden block `index` is a mine, or
+    /// that it is safe.
+    pub fn proves(&self, index: usize, is_mine: bool) -> bool {
+        let mut assumed = self.clone();
+        // cut out of the board, a block is as good as safe
+        assumed.cells[index] = if is_mine { Cell::Void } else { Cell::Mine };
+        assumed.count_solutions(1) == 0
+    }
+    /// Whether the mines shown are more than the mine count or than a number
+    /// around them, or a number can't get its mines anymore.
+    fn is_broken(&self) -> bool {
+        let shown_mines = self.cells.iter().filter(|&&cell| cell == Cell::Mine).count();
+        shown_mines > self.mine_count || (0..self.cells.len()).any(|index| match self.cells[index] {
+            Cell::Shown(tip) => {
+                let count = |wanted: Cell| self.neighbours[index].iter().filter(|&&i| self.cells[i] == wanted).count();
+                count(Cell::Mine) > tip || count(Cell::Mine) + count(Cell::Hidden) < tip
+            }
+            _ => false,
+        })
+    }
+    /// The number, its hidden neighbours and the mines still missing among
+    /// them, for every shown number that touches a hidden block.
+    fn constraints(&self) -> Vec<(usize, Vec<usize>, usize)> {
+        let mut constraints = vec![];
+        for (index, cell) in self.cells.iter().enumerate() {
+            if let Cell::Shown(tip) = cell {
+                let hidden: Vec<usize> = self.neighbours[index].iter().copied()
+                    .filter(|&i| self.cells[i] == Cell::Hidden)
+                    .collect();
+                let known = self.neighbours[index].iter().filter(|&&i| self.cells[i] == Cell::Mine).count();
+                if !hidden.is_empty() && *tip >= known {
+                    constraints.push((index, hidden, tip - known));
+                }
+            }
+        }
+        constraints
+    }
+    /// Opens a block using the real board, flooding through empty blocks.
+    fn reveal(&mut self, index: usize, truth: &[Option<usize>]) {
+        let mut stack = vec![index];
+        while let Some(index) = stack.pop() {
+            if self.cells[index] != Cell::Hidden {
+                continue;
+            }
+            match truth[index] {
+                None => self.cells[index] = Cell::Mine,
+                Some(tip) => {
+                    self.cells[index] = Cell::Shown(tip);
+                    if tip == 0 {
+                        stack.extend(self.neighbours[index].iter().copied());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Backtracking over the hidden blocks next to numbers, see `count_solutions`.
+struct SolutionCounter {
+    /// the constraints around each of those blocks
+    touching: Vec<Vec<usize>>,
+    /// mines still to place and blocks still open, per constraint
+    remaining: Vec<usize>,
+    unassigned: Vec<usize>,
+    /// hidden blocks away from every number
+    free: usize,
+    mines_left: usize,
+    limit: usize,
+    found: usize,
+    /// whether each of those blocks holds a mine in the layout being built
+    mines: Vec<bool>,
+    /// by the mines next to numbers: the layouts found, and how many of them
+    /// have a mine on each of those blocks, see `exact_probabilities`
+    tally: Option<Vec<(f64, Vec<f64>)>>,
+    /// steps left before the search gives up
+    steps: usize,
+}
+
+impl SolutionCounter {
+    fn search(&mut self, next: usize, placed: usize) {
+        if self.found >= self.limit || self.steps == 0 {
+            return;
+        }
+        self.steps -= 1;
+        if next == self.touching.len() {
+            if self.remaining.iter().all(|&mines| mines == 0) && self.mines_left - placed <= self.free {
+                match &mut self.tally {
+                    Some(tally) => {
+                        let (layouts, mines) = &mut tally[placed];
+                        *layouts += 1.0;
+                        for (count, _) in mines.iter_mut().zip(self.mines.iter()).filter(|(_, &is_mine)| is_mine) {
+                            *count += 1.0;
+                        }
+                    }
+                    None => self.found += binomial(self.free, self.mines_left - placed, self.limit),
+                }
+            }
+            return;
+        }
+        let ids = self.touching[next].clone();
+        for &id in ids.iter() {
+            self.unassigned[id] -= 1;
+        }
+        // safe when every constraint can still get its mines elsewhere
+        if ids.iter().all(|&id| self.unassigned[id] >= self.remaining[id]) {
+            self.search(next + 1, placed);
+        }
+        if placed < self.mines_left && ids.iter().all(|&id| self.remaining[id] > 0) {
+            for &id in ids.iter() {
+                self.remaining[id] -= 1;
+            }
+            if ids.iter().all(|&id| self.unassigned[id] >= self.remaining[id]) {
+                self.mines[next] = true;
+                self.search(next + 1, placed + 1);
+                self.mines[next] = false;
+            }
+            for &id in ids.iter() {
+                self.remaining[id] += 1;
+            }
+        }
+        for &id in ids.iter() {
+            self.unassigned[id] += 1;
+        }
+    }
+}
+
+/// `n` choose `k`, stopping once it reaches `limit`.
+fn binomial(n: usize, k: usize, limit: usize) -> usize {
+    let k = k.min(n - k);
+    let mut result: u128 = 1;
+    for i in 0..k {
+        result = result * (n - i) as u128 / (i + 1) as u128;
+        if result >= limit as u128 {
+            return limit;
+        }
+    }
+    result as usize
+}
+
+/// The natural log of `n` choose `k`.
+fn ln_binomial(n: usize, k: usize) -> f64 {
+    (0..k).map(|i| ((n - i) as f64 / (i + 1) as f64).ln()).sum()
+}
+
+/// Whether the whole board can be cleared from `start` without ever guessing.
+pub fn is_solvable_from(mp: &MinePlayground, start: usize) -> bool {
+    let truth = block_truth(mp);
+    if truth[start].is_none() {
+        return false;
+    }
+    let mut knowledge = Knowledge::from_playground(mp);
+    for cell in knowledge.cells.iter_mut() {
+        if *cell != Cell::Void {
+            *cell = Cell::Hidden;
+        }
+    }
+    knowledge.reveal(start, &truth);
+    solve(knowledge, &truth)
+}
+
+/// Like `is_solvable_from`, starting from the blocks already shown.
+pub fn is_solvable(mp: &MinePlayground) -> bool {
+    solve(Knowledge::from_playground(mp), &block_truth(mp))
+}
+
+/// The tip of every block, `None` for mines.
+fn block_truth(mp: &MinePlayground) -> Vec<Option<usize>> {
+    (0..mp.shape.block_count()).map(|index| match mp.block_at(index).btype {
+        BlockType::Mine(_) => None,
+        BlockType::Tip(val) => Some(val),
+        BlockType::Space => Some(0),
+    }).collect()
+}
+
+/// Plays the deductions until the board is cleared or nothing is certain.
+fn solve(mut knowledge: Knowledge, truth: &[Option<usize>]) -> bool {
+    loop {
+        if knowledge.cells.iter().all(|&cell| cell != Cell::Hidden) {
+            return true;
+        }
+        let deductions = knowledge.deduce();
+        if deductions.is_empty() {
+            return false;
+        }
+        for deduction in deductions {
+            if deduction.is_mine {
+                knowledge.cells[deduction.index] = Cell::Mine;
+            } else {
+                knowledge.reveal(deduction.index, truth);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mine_core::Position;
+    #[test]
+    fn test_deduce_subset() {
+        // opening the bottom rows leaves 1-1-2-1-1 under a hidden top row
+        let mut layout = vec![false; 25];
+        layout[1] = true;
+        layout[3] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&Position::new(2, 4, 0));
+        let deductions = Knowledge::from_playground(&mp).deduce();
+        assert!(deductions.contains(&Deduction { index: 2, is_mine: false }));
+        assert!(deductions.iter().all(|d| d.is_mine == layout[d.index]));
+    }
+    #[test]
+    fn test_count_solutions() {
+        let mut layout = vec![false; 25];
+        layout[1] = true;
+        layout[3] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&Position::new(2, 4, 0));
+        assert_eq!(Knowledge::from_playground(&mp).count_solutions(10), 1);
+        // only the mine is left hidden, the flag on it changes nothing
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&Position::new(4, 4, 0));
+        mp.right_click(&Position::new(0, 0, 0));
+        assert_eq!(Knowledge::from_playground(&mp).count_solutions(10), 1);
+        let mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        assert_eq!(Knowledge::from_playground(&mp).count_solutions(10), 10);
+        assert_eq!(Knowledge::from_playground(&mp).count_solutions(100), 25);
+    }
+    #[test]
+    fn test_mine_probabilities() {
+        // three mines under a shown top half, one of them away from the numbers
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        layout[6] = true;
+        layout[8] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        for index in 10..25 {
+            mp.show(&mp.shape.position(index)).unwrap();
+        }
+        let knowledge = Knowledge::from_playground(&mp);
+        // every way to put the mines on the bottom two rows that the numbers allow
+        let mut layouts = vec![];
+        for bits in 0u32..1 << 10 {
+            let mut cells = knowledge.cells.clone();
+            for index in (0..10).filter(|&index| bits >> index & 1 == 1) {
+                cells[index] = Cell::Mine;
+            }
+            let fits = (10..15).all(|index| match cells[index] {
+                Cell::Shown(tip) => knowledge.neighbours[index].iter().filter(|&&i| cells[i] == Cell::Mine).count() == tip,
+                _ => true,
+            });
+            if bits.count_ones() == 3 && fits {
+                layouts.push(bits);
+            }
+        }
+        let odds = knowledge.mine_probabilities();
+        for (index, chance) in odds[..10].iter().enumerate() {
+            let mined = layouts.iter().filter(|&&bits| bits >> index & 1 == 1).count();
+            assert!((chance - mined as f64 / layouts.len() as f64).abs() < 1e-9);
+        }
+        assert_eq!(odds[12], 1.0);
+        assert!((odds[..10].iter().sum::<f64>() - 3.0).abs() < 1e-9);
+    }
+    #[test]
+    fn test_certain() {
+        // a 1-2-1 under a hidden top row, with holes at its ends
+        let mut layout = vec![false; 25];
+        layout[21] = true;
+        layout[23] = true;
+        let mut mask = vec![true; 25];
+        mask[15] = false;
+        mask[19] = false;
+        let shape = BoardShape::with_mask(5, 5, crate::topology::Topology::Rectangle, mask);
+        let mut mp = MinePlayground::from_layout(shape, &layout).unwrap();
+        for index in (0..20).filter(|&index| index != 15 && index != 19) {
+            mp.show(&mp.shape.position(index)).unwrap();
+        }
+        let knowledge = Knowledge::from_playground(&mp);
+        assert!(knowledge.deduce().is_empty());
+        let mut certain = knowledge.certain();
+        certain.sort_by_key(|deduction| deduction.index);
+        assert_eq!(certain, (20..25).map(|index| Deduction { index, is_mine: layout[index] }).collect::<Vec<_>>());
+    }
+    #[test]
+    fn test_is_solvable_from() {
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        assert!(is_solvable_from(&MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap(), 24));
+        // the opening is only three numbers wide, far too little to place two mines
+        layout[0] = false;
+        layout[14] = true;
+        layout[22] = true;
+        assert!(!is_solvable_from(&MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap(), 24));
+        // with the blocks next to the mines shown, no guess is left
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        mp.click(&Position::new(4, 4, 0));
+        assert!(!is_solvable(&mp));
+        for index in [9, 13, 17, 21, 23] {
+            mp.show(&mp.shape.position(index)).unwrap();
+        }
+        assert!(is_solvable(&mp));
+    }
+}
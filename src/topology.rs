@@ -0,0 +1,87 @@
+use crate::neighbourhood::Neighbourhood;
+
+/// How the blocks of a board connect to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Topology {
+    #[default]
+    Rectangle,
+    /// left and right edges are adjacent, so are top and bottom ones
+    Torus,
+    /// hexagons in rows, odd rows shifted half a block to the right
+    Hex,
+}
+
+impl Topology {
+    pub fn from_name(name: &str) -> Result<Topology, String> {
+        match name {
+            "rectangle" => Ok(Topology::Rectangle),
+            "torus" => Ok(Topology::Torus),
+            "hex" => Ok(Topology::Hex),
+            _ => Err(format!("unknown topology `{}`", name)),
+        }
+    }
+    /// Square grids take their neighbours from `rule`, hex ones ignore it.
+    pub fn neighbours(&self, x: usize, y: usize, width: usize, height: usize, rule: Neighbourhood) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Rectangle | Topology::Torus => {
+                let (width, height) = (width as i64, height as i64);
+                rule.offsets().iter().filter_map(|&(dx, dy)| {
+                    let (cur_x, cur_y) = (x as i64 + dx, y as i64 + dy);
+                    if self.wraps() {
+                        Some((cur_x.rem_euclid(width) as usize, cur_y.rem_euclid(height) as usize))
+                    } else if (0..width).contains(&cur_x) && (0..height).contains(&cur_y) {
+                        Some((cur_x as usize, cur_y as usize))
+                    } else {
+                        None
+                    }
+                }).collect()
+            }
+            Topology::Hex => {
+                // the two blocks above and below sit left or right depending on the row
+                let shift = y % 2;
+                let mut r = vec![];
+                if x > 0 { r.push((x - 1, y)); }
+                if x + 1 < width { r.push((x + 1, y)); }
+                for &row in [y.wrapping_sub(1), y + 1].iter() {
+                    if row >= height {
+                        continue;
+                    }
+                    if x + shift > 0 { r.push((x + shift - 1, row)); }
+                    if x + shift < width { r.push((x + shift, row)); }
+                }
+                r
+            }
+        }
+    }
+    pub fn max_neighbours(&self, rule: Neighbourhood) -> usize {
+        match self {
+            Topology::Hex => 6,
+            _ => rule.offsets().len(),
+        }
+    }
+    /// Whether the board has no edges, so the view can scroll around it.
+    pub fn wraps(&self) -> bool {
+        *self == Topology::Torus
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_torus_neighbours() {
+        let corner = Topology::Torus.neighbours(0, 0, 5, 6, Neighbourhood::Classic);
+        assert_eq!(corner.len(), 8);
+        assert!(corner.contains(&(4, 5)));
+        assert!(corner.contains(&(1, 5)));
+        assert!(corner.contains(&(4, 0)));
+        assert_eq!(Topology::Rectangle.neighbours(0, 0, 5, 6, Neighbourhood::Classic).len(), 3);
+    }
+    #[test]
+    fn test_hex_neighbours() {
+        let rule = Neighbourhood::Classic;
+        assert_eq!(Topology::Hex.neighbours(2, 2, 5, 5, rule), vec![(1, 2), (3, 2), (1, 1), (2, 1), (1, 3), (2, 3)]);
+        assert_eq!(Topology::Hex.neighbours(2, 1, 5, 5, rule), vec![(1, 1), (3, 1), (2, 0), (3, 0), (2, 2), (3, 2)]);
+        assert_eq!(Topology::Hex.neighbours(4, 1, 5, 5, rule), vec![(3, 1), (4, 0), (4, 2)]);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::mine_core::{ BlockStatus, ClickResult, MinePlayground, Position };
+
+pub const PLAYERS: usize = 2;
+
+/// What hitting a mine does to the player who hit it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MineRule {
+    /// players keep their turn until they hit a mine
+    PassTurn,
+    /// players take turns after every click, a mine costs that many points
+    CostPoints(i64),
+}
+
+/// Two players on one board: every block a click opens scores a point for the
+/// player whose turn it is. The board keeps going after mines are hit, until
+/// every safe block is open.
+#[derive(Debug, Clone)]
+pub struct Versus {
+    pub rule: MineRule,
+    /// the player to click, from 0
+    pub turn: usize,
+    pub scores: [i64; PLAYERS],
+    /// who opened every block, indexed like `BoardShape::index`
+    pub revealed_by: Vec<Option<usize>>,
+    /// who flagged every block, flags are only notes of their player
+    pub flagged_by: Vec<Option<usize>>,
+}
+
+impl Versus {
+    /// Gives `mp` more lives than it has mines, so no mine ends the game.
+    /// Blocks already shown belong to nobody.
+    pub fn new(mp: &mut MinePlayground, rule: MineRule) -> Versus {
+        mp.lives = mp.mine_counts().iter().sum::<usize>() + 1;
+        let block_count = mp.shape.block_count();
+        Versus { rule, turn: 0, scores: [0; PLAYERS], revealed_by: vec![None; block_count], flagged_by: vec![None; block_count] }
+    }
+    /// Opens a block for the player whose turn it is, over any flag on it.
+    pub fn reveal(&mut self, mp: &mut MinePlayground, pos: &Position) -> ClickResult {
+        if !mp.shape.is_playable(pos) || mp.block(pos).bstatus == BlockStatus::Shown {
+            return ClickResult::NothingHappened;
+        }
+        mp.set_flags(pos, 0);
+        self.flagged_by[mp.shape.index(pos)] = None;
+        let hidden: Vec<usize> = (0..self.revealed_by.len()).filter(|&index| mp.block_at(index).bstatus != BlockStatus::Shown).collect();
+        let result = mp.click(pos);
+        for index in hidden.into_iter().filter(|&index| mp.block_at(index).bstatus == BlockStatus::Shown) {
+            self.revealed_by[index] = Some(self.turn);
+            self.scores[self.turn] += (mp.block_at(index).btype.mine_count() == 0) as i64;
+        }
+        match (&result, self.rule) {
+            (ClickResult::MineHit { .. }, MineRule::PassTurn) => self.pass_turn(),
+            (ClickResult::MineHit { .. }, MineRule::CostPoints(points)) => {
+                self.scores[self.turn] -= points;
+                self.pass_turn();
+            }
+            (_, MineRule::CostPoints(_)) => self.pass_turn(),
+            _ => {}
+        }
+        result
+    }
+    /// Puts a flag of the player whose turn it is, or takes it away. Flags of
+    /// the other player stay.
+    pub fn flag(&mut self, mp: &mut MinePlayground, pos: &Position) {
+        if !mp.shape.is_playable(pos) || mp.block(pos).bstatus == BlockStatus::Shown {
+            return;
+        }
+        let index = mp.shape.index(pos);
+        match self.flagged_by[index] {
+            None => {
+                self.flagged_by[index] = Some(self.turn);
+                mp.set_flags(pos, 1);
+            }
+            Some(player) if player == self.turn => {
+                self.flagged_by[index] = None;
+                mp.set_flags(pos, 0);
+            }
+            Some(_) => {}
+        }
+    }
+    /// The player ahead, `None` on a draw.
+    pub fn leader(&self) -> Option<usize> {
+        let best = *self.scores.iter().max().unwrap();
+        let leaders: Vec<usize> = (0..PLAYERS).filter(|&player| self.scores[player] == best).collect();
+        if leaders.len() == 1 { Some(leaders[0]) } else { None }
+    }
+    pub fn results(&self) -> String {
+        match self.leader() {
+            Some(player) => format!("player {} wins {} to {}", player + 1, self.scores[player], self.scores[1 - player]),
+            None => format!("draw at {}", self.scores[0]),
+        }
+    }
+    fn pass_turn(&mut self) {
+        self.turn = (self.turn + 1) % PLAYERS;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::BoardShape;
+    #[test]
+    fn test_versus() {
+        // three mines along the bottom row, the rows above open at once
+        let mut layout = vec![false; 25];
+        layout[0] = true;
+        layout[2] = true;
+        layout[4] = true;
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        let mut versus = Versus::new(&mut mp, MineRule::PassTurn);
+        versus.flag(&mut mp, &Position::new(0, 0, 0));
+        assert!(matches!(versus.reveal(&mut mp, &Position::new(2, 4, 0)), ClickResult::NothingHappened));
+        assert_eq!((versus.turn, versus.scores), (0, [20, 0]));
+        assert!(matches!(versus.reveal(&mut mp, &Position::new(4, 0, 0)), ClickResult::MineHit { .. }));
+        assert_eq!(versus.turn, 1);
+        // the flag of player 1 is only a note, player 2 can't take it away
+        versus.flag(&mut mp, &Position::new(0, 0, 0));
+        assert_eq!(versus.flagged_by[0], Some(0));
+        assert!(matches!(versus.reveal(&mut mp, &Position::new(1, 0, 0)), ClickResult::NothingHappened));
+        assert!(matches!(versus.reveal(&mut mp, &Position::new(3, 0, 0)), ClickResult::Win));
+        assert_eq!(versus.scores, [20, 2]);
+        assert_eq!((versus.revealed_by[4], versus.revealed_by[3]), (Some(0), Some(1)));
+        assert_eq!(versus.results(), "player 1 wins 20 to 2");
+        let mut mp = MinePlayground::from_layout(BoardShape::rectangle(5, 5), &layout).unwrap();
+        let mut versus = Versus::new(&mut mp, MineRule::CostPoints(5));
+        versus.reveal(&mut mp, &Position::new(0, 0, 0));
+        assert_eq!((versus.turn, versus.scores), (1, [-5, 0]));
+    }
+}